@@ -0,0 +1,143 @@
+//! Reading `CMakeCache.txt` files of existing configurations.
+
+use std::collections::HashMap;
+use std::io;
+use std::path::Path;
+
+/// The entries of a `CMakeCache.txt`, by name.
+pub struct CMakeCache {
+    entries: HashMap<String, Entry>,
+}
+
+pub struct Entry {
+    pub value: String,
+    /// Like BOOL or FILEPATH, empty if the line didn't say
+    pub ty: String,
+    /// The `//` comment lines above it
+    pub help: String,
+}
+
+impl Entry {
+    /// Whether cmake keeps it for itself, rather than for users to set.
+    pub fn is_internal(&self) -> bool {
+        self.ty == "INTERNAL" || self.ty == "STATIC"
+    }
+}
+
+impl CMakeCache {
+    pub fn read(path: &Path) -> io::Result<Self> {
+        let text = std::fs::read_to_string(path)?;
+        Ok(Self::parse(&text))
+    }
+    /// Parses `KEY:TYPE=VALUE` lines, skipping comments and anything malformed.
+    pub fn parse(text: &str) -> Self {
+        let mut entries = HashMap::new();
+        let mut help = Vec::new();
+        for line in text.lines() {
+            let line = line.trim_start();
+            if let Some(comment) = line.strip_prefix("//") {
+                help.push(comment.to_string());
+                continue;
+            }
+            if line.is_empty() || line.starts_with('#') {
+                help.clear();
+                continue;
+            }
+            let (key, value) = match line.find('=') {
+                Some(pos) => (&line[..pos], &line[pos + 1..]),
+                None => continue,
+            };
+            // The type is optional
+            let (key, ty) = match key.rfind(':') {
+                Some(pos) => (&key[..pos], &key[pos + 1..]),
+                None => (key, ""),
+            };
+            let entry = Entry {
+                value: value.to_string(),
+                ty: ty.to_string(),
+                help: help.join("\n"),
+            };
+            help.clear();
+            entries.insert(key.trim_matches('"').to_string(), entry);
+        }
+        CMakeCache { entries }
+    }
+    /// All entries, internal ones included, sorted by name.
+    pub fn entries(&self) -> Vec<(&str, &Entry)> {
+        let mut entries: Vec<(&str, &Entry)> = self
+            .entries
+            .iter()
+            .map(|(key, entry)| (&key[..], entry))
+            .collect();
+        entries.sort_by_key(|&(key, _)| key);
+        entries
+    }
+    /// The entries users get to set, sorted by name.
+    pub fn variables(&self) -> Vec<(&str, &str)> {
+        self.entries()
+            .into_iter()
+            .filter(|&(_, entry)| !entry.is_internal())
+            .map(|(key, entry)| (key, &entry.value[..]))
+            .collect()
+    }
+    /// Whether `key` is marked as advanced, which hides it in cmake-gui unless asked for.
+    pub fn is_advanced(&self, key: &str) -> bool {
+        self.get(&format!("{}-ADVANCED", key)) == Some("1")
+    }
+    pub fn get(&self, key: &str) -> Option<&str> {
+        self.entries
+            .get(key)
+            .map(|entry| &entry.value[..])
+            .filter(|s| !s.is_empty())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const TEXT: &str = "# This is the CMakeCache file.\n\
+                        \n\
+                        //Build type\n\
+                        //of the project\n\
+                        CMAKE_BUILD_TYPE:STRING=Debug\n\
+                        \"QUOTED:KEY\":BOOL=ON\n\
+                        UNTYPED=1\n\
+                        EMPTY:PATH=\n\
+                        malformed line\n\
+                        CMAKE_COMMAND:INTERNAL=/usr/bin/cmake\n\
+                        CMAKE_BUILD_TYPE-ADVANCED:INTERNAL=1\n";
+
+    #[test]
+    fn entries() {
+        let cache = CMakeCache::parse(TEXT);
+        assert_eq!(cache.get("CMAKE_BUILD_TYPE"), Some("Debug"));
+        assert_eq!(cache.get("QUOTED:KEY"), Some("ON"));
+        assert_eq!(cache.get("UNTYPED"), Some("1"));
+        assert_eq!(cache.get("EMPTY"), None);
+        assert_eq!(cache.get("malformed line"), None);
+        let entries = cache.entries();
+        let (_, entry) = entries
+            .iter()
+            .find(|&&(key, _)| key == "CMAKE_BUILD_TYPE")
+            .unwrap();
+        assert_eq!(entry.ty, "STRING");
+        assert_eq!(entry.help, "Build type\nof the project");
+    }
+
+    #[test]
+    fn variables_leave_out_internal_entries() {
+        let cache = CMakeCache::parse(TEXT);
+        assert_eq!(
+            cache.variables(),
+            [
+                ("CMAKE_BUILD_TYPE", "Debug"),
+                ("EMPTY", ""),
+                ("QUOTED:KEY", "ON"),
+                ("UNTYPED", "1")
+            ]
+        );
+        assert!(cache.is_advanced("CMAKE_BUILD_TYPE"));
+        assert!(!cache.is_advanced("UNTYPED"));
+    }
+}
@@ -0,0 +1,237 @@
+//! Just enough of the CMake language to see what commands a `CMakeLists.txt` runs.
+//!
+//! Nothing gets evaluated, so arguments keep their `${VARIABLE}` references.
+
+/// A command invocation, like `project(foo LANGUAGES CXX)`.
+pub struct Command {
+    /// Lowercase, command names are case insensitive
+    pub name: String,
+    pub args: Vec<String>,
+}
+
+/// The commands of a script, in order, skipping whatever doesn't parse.
+pub fn parse(text: &str) -> Vec<Command> {
+    let chars: Vec<char> = text.chars().collect();
+    let mut parser = Parser {
+        chars: &chars,
+        pos: 0,
+    };
+    let mut commands = Vec::new();
+    while let Some(c) = parser.peek() {
+        if c == '#' {
+            parser.comment();
+        } else if c.is_alphabetic() || c == '_' {
+            let name = parser.identifier();
+            while parser.peek().is_some_and(|c| c == ' ' || c == '\t') {
+                parser.pos += 1;
+            }
+            if parser.peek() == Some('(') {
+                parser.pos += 1;
+                commands.push(Command {
+                    name: name.to_lowercase(),
+                    args: parser.arguments(),
+                });
+            }
+        } else {
+            parser.pos += 1;
+        }
+    }
+    commands
+}
+
+struct Parser<'a> {
+    chars: &'a [char],
+    pos: usize,
+}
+
+impl<'a> Parser<'a> {
+    fn peek(&self) -> Option<char> {
+        self.chars.get(self.pos).cloned()
+    }
+    fn identifier(&mut self) -> String {
+        let start = self.pos;
+        while self.peek().is_some_and(|c| c.is_alphanumeric() || c == '_') {
+            self.pos += 1;
+        }
+        self.chars[start..self.pos].iter().collect()
+    }
+    /// Skips a line comment, or a bracket comment like `#[[ ... ]]`.
+    fn comment(&mut self) {
+        self.pos += 1;
+        if self.peek() == Some('[') && self.bracket().is_some() {
+            return;
+        }
+        while self.peek().is_some_and(|c| c != '\n') {
+            self.pos += 1;
+        }
+    }
+    /// The content of a bracket argument like `[==[ ... ]==]`, if one starts here.
+    fn bracket(&mut self) -> Option<String> {
+        let mut open = self.pos + 1;
+        while self.chars.get(open) == Some(&'=') {
+            open += 1;
+        }
+        if self.chars.get(open) != Some(&'[') {
+            return None;
+        }
+        let level = open - self.pos - 1;
+        let mut close = vec![']'];
+        close.extend(std::iter::repeat_n('=', level));
+        close.push(']');
+        let mut start = open + 1;
+        // A newline right after the opening bracket isn't part of the content
+        if self.chars.get(start) == Some(&'\n') {
+            start += 1;
+        }
+        let end = (start..self.chars.len())
+            .find(|&i| self.chars[i..].starts_with(&close))
+            .unwrap_or(self.chars.len());
+        self.pos = (end + close.len()).min(self.chars.len());
+        Some(self.chars[start.min(end)..end].iter().collect())
+    }
+    /// The arguments up to the closing parenthesis of a command.
+    ///
+    /// Nested parentheses, like those of `if()` conditions, are arguments of their own.
+    fn arguments(&mut self) -> Vec<String> {
+        let mut args = Vec::new();
+        let mut depth = 0;
+        while let Some(c) = self.peek() {
+            match c {
+                '(' => {
+                    self.pos += 1;
+                    depth += 1;
+                    args.push("(".to_string());
+                }
+                ')' => {
+                    self.pos += 1;
+                    if depth == 0 {
+                        break;
+                    }
+                    depth -= 1;
+                    args.push(")".to_string());
+                }
+                '#' => self.comment(),
+                '"' => args.push(self.quoted()),
+                '[' => match self.bracket() {
+                    Some(arg) => args.push(arg),
+                    None => args.push(self.unquoted()),
+                },
+                c if c.is_whitespace() => self.pos += 1,
+                _ => args.push(self.unquoted()),
+            }
+        }
+        args
+    }
+    fn quoted(&mut self) -> String {
+        self.pos += 1;
+        let mut arg = String::new();
+        while let Some(c) = self.peek() {
+            self.pos += 1;
+            match c {
+                '"' => break,
+                '\\' => match self.peek() {
+                    // A line continuation
+                    Some('\n') => self.pos += 1,
+                    Some(c) => {
+                        self.pos += 1;
+                        arg.push(escaped(c));
+                    }
+                    None => {}
+                },
+                _ => arg.push(c),
+            }
+        }
+        arg
+    }
+    fn unquoted(&mut self) -> String {
+        let mut arg = String::new();
+        while let Some(c) = self.peek() {
+            match c {
+                '(' | ')' | '#' | '"' => break,
+                c if c.is_whitespace() => break,
+                '\\' => {
+                    self.pos += 1;
+                    if let Some(c) = self.peek() {
+                        self.pos += 1;
+                        arg.push(escaped(c));
+                    }
+                }
+                _ => {
+                    self.pos += 1;
+                    arg.push(c);
+                }
+            }
+        }
+        arg
+    }
+}
+
+fn escaped(c: char) -> char {
+    match c {
+        'n' => '\n',
+        't' => '\t',
+        'r' => '\r',
+        '0' => '\0',
+        c => c,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn commands(text: &str) -> Vec<(String, Vec<String>)> {
+        parse(text)
+            .into_iter()
+            .map(|command| (command.name, command.args))
+            .collect()
+    }
+
+    fn strings(strs: &[&str]) -> Vec<String> {
+        strs.iter().map(|s| s.to_string()).collect()
+    }
+
+    #[test]
+    fn names_are_lowercase() {
+        assert_eq!(
+            commands("PROJECT(foo LANGUAGES CXX)\nAdd_Executable (foo main.cpp)"),
+            [
+                ("project".to_string(), strings(&["foo", "LANGUAGES", "CXX"])),
+                ("add_executable".to_string(), strings(&["foo", "main.cpp"])),
+            ]
+        );
+    }
+
+    #[test]
+    fn quoted_and_bracket_arguments() {
+        assert_eq!(
+            commands("message(\"a \\\"b\\\"\\tc\" [==[x ]] y]==] ${VAR})"),
+            [(
+                "message".to_string(),
+                strings(&["a \"b\"\tc", "x ]] y", "${VAR}"])
+            )]
+        );
+    }
+
+    #[test]
+    fn comments_are_skipped() {
+        let text = "# option(A \"a\" ON)\n#[[\nset(B 1)\n]]\nset(C 2 # not an argument\n)";
+        assert_eq!(commands(text), [("set".to_string(), strings(&["C", "2"]))]);
+    }
+
+    #[test]
+    fn nested_parentheses_are_arguments() {
+        assert_eq!(
+            commands("if((A OR B) AND C)"),
+            [(
+                "if".to_string(),
+                strings(&["(", "A", "OR", "B", ")", "AND", "C"])
+            )]
+        );
+    }
+
+    #[test]
+    fn words_without_parentheses_are_no_commands() {
+        assert!(commands("just some words").is_empty());
+    }
+}
@@ -0,0 +1,346 @@
+//! Completion scripts for bash, zsh and fish.
+//!
+//! The options come from the same place as the usage, what depends on the project gets asked for
+//! with `mkqcb completions configs|profiles <project_dir>` as they complete.
+
+use getopts::{self, HasArg, Occur};
+
+/// The subcommands that take configuration names after the project.
+const CONFIG_SUBCOMMANDS: [&str; 6] = ["remove", "link", "diff", "cache", "install", "time-trace"];
+
+/// The options whose values are configuration names or patterns for them.
+const CONFIG_OPTIONS: [&str; 3] = ["only", "configs", "link-compile-commands"];
+
+/// An option as the completion scripts see it.
+struct Spec {
+    short: String,
+    long: String,
+    desc: String,
+    /// What to call the value of an option, empty for flags
+    hint: String,
+    multi: bool,
+}
+
+impl Spec {
+    /// The values to offer, for options with a fixed set of them like `auto|always|never`.
+    fn choices(&self) -> Option<Vec<&str>> {
+        let is_choice = |c: char| c.is_ascii_lowercase() || c.is_ascii_digit() || "|-+".contains(c);
+        if self.hint.contains('|') && self.hint.chars().all(is_choice) {
+            Some(self.hint.split('|').collect())
+        } else {
+            None
+        }
+    }
+    fn takes_files(&self) -> bool {
+        ["PATH", "FILE", "DIR"].contains(&&self.hint[..])
+    }
+    fn dashed(&self) -> Vec<String> {
+        let mut names = Vec::new();
+        if !self.short.is_empty() {
+            names.push(format!("-{}", self.short));
+        }
+        if !self.long.is_empty() {
+            names.push(format!("--{}", self.long));
+        }
+        names
+    }
+}
+
+/// getopts options that remember what they are, so there's something to complete.
+#[derive(Default)]
+pub struct Options {
+    getopts: getopts::Options,
+    specs: Vec<Spec>,
+}
+
+impl Options {
+    pub fn new() -> Options {
+        Options::default()
+    }
+    fn opt(
+        &mut self,
+        short: &str,
+        long: &str,
+        desc: &str,
+        hint: &str,
+        has_arg: HasArg,
+        occur: Occur,
+    ) -> &mut Options {
+        self.getopts.opt(short, long, desc, hint, has_arg, occur);
+        self.specs.push(Spec {
+            short: short.to_string(),
+            long: long.to_string(),
+            desc: desc.to_string(),
+            hint: hint.to_string(),
+            multi: occur == Occur::Multi,
+        });
+        self
+    }
+    pub fn optflag(&mut self, short: &str, long: &str, desc: &str) -> &mut Options {
+        self.opt(short, long, desc, "", HasArg::No, Occur::Optional)
+    }
+    pub fn optflagmulti(&mut self, short: &str, long: &str, desc: &str) -> &mut Options {
+        self.opt(short, long, desc, "", HasArg::No, Occur::Multi)
+    }
+    pub fn optopt(&mut self, short: &str, long: &str, desc: &str, hint: &str) -> &mut Options {
+        self.opt(short, long, desc, hint, HasArg::Yes, Occur::Optional)
+    }
+    pub fn optmulti(&mut self, short: &str, long: &str, desc: &str, hint: &str) -> &mut Options {
+        self.opt(short, long, desc, hint, HasArg::Yes, Occur::Multi)
+    }
+    pub fn optflagopt(&mut self, short: &str, long: &str, desc: &str, hint: &str) -> &mut Options {
+        self.opt(short, long, desc, hint, HasArg::Maybe, Occur::Optional)
+    }
+    pub fn parse(&self, args: Vec<String>) -> getopts::Result {
+        self.getopts.parse(args)
+    }
+    pub fn usage(&self, brief: &str) -> String {
+        self.getopts.usage(brief)
+    }
+}
+
+/// The completion script for `shell`, if it's one there is a script for.
+pub fn script(shell: &str, opts: &Options, subcommands: &[&str]) -> Option<String> {
+    match shell {
+        "bash" => Some(bash(opts, subcommands)),
+        "zsh" => Some(zsh(opts, subcommands)),
+        "fish" => Some(fish(opts, subcommands)),
+        _ => None,
+    }
+}
+
+/// Quotes `text` for a shell where nothing is special inside single quotes, like bash and zsh.
+fn quote(text: &str) -> String {
+    format!("'{}'", text.replace('\'', "'\\''"))
+}
+
+fn bash(opts: &Options, subcommands: &[&str]) -> String {
+    let mut options = Vec::new();
+    let mut valued = Vec::new();
+    let mut cases = String::new();
+    for spec in &opts.specs {
+        let names = spec.dashed();
+        options.extend(names.iter().cloned());
+        if spec.hint.is_empty() {
+            continue;
+        }
+        valued.extend(names.iter().cloned());
+        let reply = if CONFIG_OPTIONS.contains(&&spec.long[..]) {
+            "COMPREPLY=($(compgen -W \"$(_mkqcb_query configs)\" -- \"$cur\"))".to_string()
+        } else if spec.long == "profile" {
+            "COMPREPLY=($(compgen -W \"$(_mkqcb_query profiles)\" -- \"$cur\"))".to_string()
+        } else if let Some(choices) = spec.choices() {
+            format!(
+                "COMPREPLY=($(compgen -W '{}' -- \"$cur\"))",
+                choices.join(" ")
+            )
+        } else {
+            // Files are as good a guess as any
+            ":".to_string()
+        };
+        cases += &format!(
+            "        {})\n            {}\n            return ;;\n",
+            names.join("|"),
+            reply
+        );
+    }
+    format!(
+        r#"# bash completion for mkqcb
+_mkqcb_query() {{
+    mkqcb completions "$1" "$project" 2>/dev/null
+}}
+
+_mkqcb() {{
+    local cur=${{COMP_WORDS[COMP_CWORD]}} prev=${{COMP_WORDS[COMP_CWORD-1]}}
+    local valued=' {valued} '
+    local words=() i
+    for ((i = 1; i < COMP_CWORD; i++)); do
+        case ${{COMP_WORDS[i]}} in
+            --) return ;;
+            -*) [[ $valued == *" ${{COMP_WORDS[i]}} "* ]] && ((i++)) ;;
+            *) words+=("${{COMP_WORDS[i]}}") ;;
+        esac
+    done
+    local project=.
+    case ${{words[0]}} in
+        ccdb) project=${{words[2]:-.}} ;;
+        {subcommand_cases}) project=${{words[1]:-.}} ;;
+    esac
+    case $prev in
+{cases}    esac
+    if [[ $cur == -* ]]; then
+        COMPREPLY=($(compgen -W '{options}' -- "$cur"))
+    elif ((${{#words[@]}} == 0)); then
+        COMPREPLY=($(compgen -W '{subcommands}' -- "$cur"))
+    elif [[ ${{words[0]}} == ccdb ]] && ((${{#words[@]}} == 1)); then
+        COMPREPLY=($(compgen -W 'merge' -- "$cur"))
+    elif [[ " {config_subcommands} " == *" ${{words[0]}} "* ]] && ((${{#words[@]}} >= 2)); then
+        COMPREPLY=($(compgen -W "$(_mkqcb_query configs)" -- "$cur"))
+    fi
+}}
+
+complete -o bashdefault -o default -F _mkqcb mkqcb
+"#,
+        valued = valued.join(" "),
+        subcommand_cases = subcommands.join("|"),
+        cases = cases,
+        options = options.join(" "),
+        subcommands = subcommands.join(" "),
+        config_subcommands = CONFIG_SUBCOMMANDS.join(" "),
+    )
+}
+
+fn zsh(opts: &Options, subcommands: &[&str]) -> String {
+    let mut specs = String::new();
+    for spec in &opts.specs {
+        // zsh takes brackets and colons as the end of the description
+        let desc = spec
+            .desc
+            .replace('[', "(")
+            .replace(']', ")")
+            .replace(':', ",");
+        let hint = spec.hint.replace(':', " ");
+        let action = if spec.hint.is_empty() {
+            String::new()
+        } else if CONFIG_OPTIONS.contains(&&spec.long[..]) {
+            format!(":{}:_mkqcb_configs", hint)
+        } else if spec.long == "profile" {
+            format!(":{}:_mkqcb_profiles", hint)
+        } else if let Some(choices) = spec.choices() {
+            format!(":{}:({})", hint, choices.join(" "))
+        } else if spec.takes_files() {
+            format!(":{}:_files", hint)
+        } else {
+            format!(":{}: ", hint)
+        };
+        for name in spec.dashed() {
+            let repeat = if spec.multi { "*" } else { "" };
+            let equals = if name.starts_with("--") && !spec.hint.is_empty() {
+                "="
+            } else {
+                ""
+            };
+            let arg = format!("{}{}{}[{}]{}", repeat, name, equals, desc, action);
+            specs += &format!("        {} \\\n", quote(&arg));
+        }
+    }
+    format!(
+        r#"#compdef mkqcb
+
+# The project is the word after the subcommand, or where we are
+_mkqcb_project() {{
+    local i found=
+    for ((i = 2; i < CURRENT; i++)); do
+        if [[ -n $found && $words[i] != -* && $words[i] != merge ]]; then
+            print -r -- $words[i]
+            return
+        fi
+        [[ " {subcommands} " == *" $words[i] "* ]] && found=1
+    done
+    print .
+}}
+
+_mkqcb_configs() {{
+    local -a configs
+    configs=(${{(f)"$(mkqcb completions configs "$(_mkqcb_project)" 2>/dev/null)"}})
+    compadd -a configs
+}}
+
+_mkqcb_profiles() {{
+    local -a profiles
+    profiles=(${{(f)"$(mkqcb completions profiles "$(_mkqcb_project)" 2>/dev/null)"}})
+    compadd -a profiles
+}}
+
+_mkqcb() {{
+    local curcontext=$curcontext state line
+    _arguments -s -S \
+{specs}        '1: :->first' \
+        '*: :->rest'
+    case $state in
+        first)
+            _alternative 'subcommands:subcommand:({subcommands})' 'projects:project:_files -/'
+            ;;
+        rest)
+            case $line[1] in
+                ccdb) (( CURRENT == 3 )) && compadd merge || _files -/ ;;
+                from-tarball) _files ;;
+                {config_subcommands}) (( ${{#line}} >= 3 )) && _mkqcb_configs || _files -/ ;;
+                *) _files -/ ;;
+            esac
+            ;;
+    esac
+}}
+
+_mkqcb "$@"
+"#,
+        subcommands = subcommands.join(" "),
+        specs = specs,
+        config_subcommands = CONFIG_SUBCOMMANDS.join("|"),
+    )
+}
+
+fn fish(opts: &Options, subcommands: &[&str]) -> String {
+    let mut lines = String::new();
+    for spec in &opts.specs {
+        let mut line = "complete -c mkqcb".to_string();
+        if !spec.short.is_empty() {
+            line += &format!(" -s {}", spec.short);
+        }
+        if !spec.long.is_empty() {
+            line += &format!(" -l {}", spec.long);
+        }
+        if !spec.hint.is_empty() {
+            line.push_str(" -r");
+            if CONFIG_OPTIONS.contains(&&spec.long[..]) {
+                line.push_str(" -f -a '(__mkqcb_query configs)'");
+            } else if spec.long == "profile" {
+                line.push_str(" -f -a '(__mkqcb_query profiles)'");
+            } else if let Some(choices) = spec.choices() {
+                line += &format!(" -f -a '{}'", choices.join(" "));
+            } else if spec.takes_files() {
+                line.push_str(" -F");
+            }
+        }
+        // Unlike bash, fish has backslash escapes inside single quotes
+        line += &format!(
+            " -d '{}'\n",
+            spec.desc.replace('\\', "\\\\").replace('\'', "\\'")
+        );
+        lines += &line;
+    }
+    format!(
+        r#"# fish completion for mkqcb
+
+# The words that aren't options, which may take some option values for words
+function __mkqcb_positionals
+    for word in (commandline -opc)[2..-1]
+        string match -q -- '-*' $word; or echo $word
+    end
+end
+
+function __mkqcb_query
+    set -l words (__mkqcb_positionals)
+    set -l project .
+    if test "$words[1]" = ccdb; and set -q words[3]
+        set project $words[3]
+    else if contains -- "$words[1]" {subcommands}; and set -q words[2]
+        set project $words[2]
+    end
+    mkqcb completions $argv[1] $project 2>/dev/null
+end
+
+function __mkqcb_wants_config
+    set -l words (__mkqcb_positionals)
+    contains -- "$words[1]" {config_subcommands}; and set -q words[2]
+end
+
+complete -c mkqcb -n __fish_use_subcommand -a '{subcommands}'
+complete -c mkqcb -n '__fish_seen_subcommand_from ccdb; and test (count (__mkqcb_positionals)) -eq 1' -f -a merge
+complete -c mkqcb -n __mkqcb_wants_config -f -a '(__mkqcb_query configs)'
+{lines}"#,
+        subcommands = subcommands.join(" "),
+        config_subcommands = CONFIG_SUBCOMMANDS.join(" "),
+        lines = lines,
+    )
+}
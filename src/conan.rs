@@ -0,0 +1,136 @@
+//! Installing the dependencies of projects that use Conan, per configuration.
+
+use super::{Compiler, Config};
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+/// The toolchain file the CMakeToolchain generator writes.
+const TOOLCHAIN: &str = "conan_toolchain.cmake";
+
+/// Whether the project has a conanfile.
+pub fn uses_conan(proj_dir: &Path) -> bool {
+    ["conanfile.py", "conanfile.txt"]
+        .iter()
+        .any(|name| proj_dir.join(name).is_file())
+}
+
+/// The Conan name of a compiler, along with its C and C++ executables.
+fn conan_compiler(compiler: &Compiler) -> Option<(&'static str, String, String)> {
+    match *compiler {
+        Compiler::Gcc => Some(("gcc", "gcc".to_string(), "g++".to_string())),
+        Compiler::Clang => Some(("clang", "clang".to_string(), "clang++".to_string())),
+        Compiler::Custom {
+            ref name,
+            ref c,
+            ref cxx,
+        } => {
+            let kind = if name.contains("clang") {
+                "clang"
+            } else if name.contains("gcc") || name.contains("g++") {
+                "gcc"
+            } else {
+                return None;
+            };
+            let c = c.clone().unwrap_or_else(|| kind.to_string());
+            let cxx = cxx.clone().unwrap_or_else(|| c.clone());
+            Some((kind, c, cxx))
+        }
+        Compiler::Toolchain { .. } => None,
+    }
+}
+
+/// The major version of a compiler, which is what Conan's settings go by.
+fn major_version(compiler: &str) -> Option<String> {
+    let output = Command::new(compiler).arg("-dumpversion").output().ok()?;
+    let version = String::from_utf8_lossy(&output.stdout);
+    let major = version.trim().split('.').next()?.to_string();
+    Some(major).filter(|major| output.status.success() && !major.is_empty())
+}
+
+/// The C++ standard library `conf` links with, by its Conan name.
+///
+/// Clang goes with libc++ when asked to, and on macOS, where there's no libstdc++ to go with.
+fn libcxx(kind: &str, conf: &Config) -> &'static str {
+    let asked_for = conf
+        .cmake_args
+        .iter()
+        .any(|arg| arg.contains("_FLAGS") && arg.contains("-stdlib=libc++"));
+    if kind == "clang" && (asked_for || cfg!(target_os = "macos")) {
+        "libc++"
+    } else {
+        "libstdc++11"
+    }
+}
+
+/// `conan install` for `conf`, to run in its build directory.
+///
+/// The settings follow the configuration, the rest comes from the default profile.
+pub fn install_command(conan: &str, conf: &Config, project_dir: &Path, dir: &Path) -> Command {
+    let mut cmd = Command::new(conan);
+    cmd.arg("install")
+        .arg(project_dir)
+        .args(["--output-folder=.", "--build=missing"]);
+    if let Some(build_type) = conf.build_type {
+        cmd.arg("-s")
+            .arg(format!("build_type={}", build_type.name()));
+    }
+    match conan_compiler(&conf.compiler) {
+        Some((kind, c, cxx)) => {
+            cmd.arg("-s").arg(format!("compiler={}", kind));
+            if let Some(version) = major_version(&c) {
+                cmd.arg("-s").arg(format!("compiler.version={}", version));
+            }
+            cmd.arg("-s")
+                .arg(format!("compiler.libcxx={}", libcxx(kind, conf)));
+            cmd.arg("-c").arg(format!(
+                "tools.build:compiler_executables={{\"c\": \"{}\", \"cpp\": \"{}\"}}",
+                c, cxx
+            ));
+        }
+        None => {
+            if let Compiler::Toolchain { ref file, .. } = conf.compiler {
+                cmd.arg("-c").arg(format!(
+                    "tools.cmake.cmaketoolchain:user_toolchain=[\"{}\"]",
+                    super::cmake_path(file)
+                ));
+            }
+        }
+    }
+    // Dependencies need the same instrumentation, or sanitizers report false positives
+    let sanitizers = super::sanitizers::from_args(&conf.cmake_args);
+    if !sanitizers.is_empty() {
+        let flag = format!("[\"-fsanitize={}\"]", sanitizers.join(","));
+        for conf_name in ["cflags", "cxxflags", "exelinkflags", "sharedlinkflags"] {
+            cmd.arg("-c")
+                .arg(format!("tools.build:{}={}", conf_name, flag));
+        }
+    }
+    cmd.current_dir(dir);
+    cmd
+}
+
+/// Where `conan install` put the toolchain file, relative to the build directory `dir`.
+///
+/// A conanfile with a layout puts it further down, in its generators folder.
+pub fn toolchain(dir: &Path) -> PathBuf {
+    fn find(dir: &Path, depth: usize) -> Option<PathBuf> {
+        if dir.join(TOOLCHAIN).is_file() {
+            return Some(PathBuf::from(TOOLCHAIN));
+        }
+        if depth == 0 {
+            return None;
+        }
+        let mut entries: Vec<PathBuf> = std::fs::read_dir(dir)
+            .ok()?
+            .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+            .filter(|path| path.is_dir())
+            .collect();
+        entries.sort();
+        entries.iter().find_map(|sub| {
+            let found = find(sub, depth - 1)?;
+            Some(Path::new(sub.file_name()?).join(found))
+        })
+    }
+    // Not finding it happens when it's on a remote machine, where there's no layout to speak of
+    find(dir, 4).unwrap_or_else(|| PathBuf::from(TOOLCHAIN))
+}
@@ -0,0 +1,209 @@
+//! Settings from `.mkqcb.toml` in the project, and `mkqcb/config.toml` in the user's config
+//! directory.
+
+use std::path::{Path, PathBuf};
+use toml::{Table, Value};
+
+pub const FILE_NAME: &str = ".mkqcb.toml";
+
+#[derive(Default)]
+pub struct ConfigFile {
+    /// Commands to run before configuring each configuration
+    pub pre_hooks: Vec<String>,
+    /// Commands to run once each configuration is configured
+    pub post_hooks: Vec<String>,
+    /// Names for sets of configurations, with the `--only` patterns that pick them
+    pub profiles: Vec<(String, String)>,
+    /// Configurations of `[configs]`, to create alongside those mkqcb comes up with
+    pub configs: Vec<ConfigSpec>,
+}
+
+/// A configuration as the config file has it, with what it `extends` still to be filled in.
+///
+/// Everything but the name is optional, it comes from the configuration extended.
+#[derive(Default)]
+pub struct ConfigSpec {
+    pub name: String,
+    /// Another configuration of `[configs]`, or one of those mkqcb comes up with
+    pub extends: Option<String>,
+    /// Only there to be extended, not created itself
+    pub is_abstract: bool,
+    pub compiler: Option<String>,
+    pub build_type: Option<String>,
+    pub generator: Option<String>,
+    /// Added after those of the configuration extended
+    pub args: Vec<String>,
+    /// Overrides the variables of the configuration extended
+    pub env: Vec<(String, String)>,
+}
+
+impl ConfigFile {
+    /// Reads the user's file, then the project's, so hooks of both run in that order.
+    ///
+    /// The project's profiles and configurations win over the user's of the same name.
+    pub fn load(proj_dir: &Path) -> Result<Self, String> {
+        let mut config = ConfigFile::default();
+        let user_file = super::config_home().map(|dir| dir.join("mkqcb/config.toml"));
+        for path in user_file.into_iter().chain(Some(proj_dir.join(FILE_NAME))) {
+            if let Some(table) = read(&path)? {
+                config
+                    .merge(&table)
+                    .map_err(|e| format!("Invalid {:?}: {}", path, e))?;
+            }
+        }
+        Ok(config)
+    }
+    fn merge(&mut self, table: &Table) -> Result<(), String> {
+        if let Some(hooks) = table.get("hooks") {
+            let hooks = hooks.as_table().ok_or("hooks should be a table")?;
+            self.pre_hooks.extend(commands(hooks, "pre")?);
+            self.post_hooks.extend(commands(hooks, "post")?);
+        }
+        if let Some(profiles) = table.get("profiles") {
+            let profiles = profiles.as_table().ok_or("profiles should be a table")?;
+            for (name, patterns) in profiles {
+                let patterns = match *patterns {
+                    Value::String(ref patterns) => patterns.clone(),
+                    Value::Array(ref patterns) => patterns
+                        .iter()
+                        .map(|p| {
+                            p.as_str()
+                                .ok_or(format!("profiles.{} should only have strings", name))
+                        })
+                        .collect::<Result<Vec<_>, _>>()?
+                        .join(","),
+                    _ => {
+                        return Err(format!(
+                            "profiles.{} should be patterns or a list of them",
+                            name
+                        ))
+                    }
+                };
+                self.profiles.retain(|(n, _)| n != name);
+                self.profiles.push((name.clone(), patterns));
+            }
+        }
+        if let Some(configs) = table.get("configs") {
+            let configs = configs.as_table().ok_or("configs should be a table")?;
+            for (name, config) in configs {
+                let config = config
+                    .as_table()
+                    .ok_or(format!("configs.{} should be a table", name))?;
+                let spec =
+                    config_spec(name, config).map_err(|e| format!("configs.{}{}", name, e))?;
+                self.configs.retain(|c| c.name != *name);
+                self.configs.push(spec);
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Errors start with the key, for the caller to put after the name of the configuration.
+fn config_spec(name: &str, table: &Table) -> Result<ConfigSpec, String> {
+    let string = |key: &str| match table.get(key) {
+        None => Ok(None),
+        Some(Value::String(value)) => Ok(Some(value.clone())),
+        Some(_) => Err(format!(".{} should be a string", key)),
+    };
+    let mut spec = ConfigSpec {
+        name: name.to_string(),
+        extends: string("extends")?,
+        compiler: string("compiler")?,
+        build_type: string("build_type")?,
+        generator: string("generator")?,
+        ..ConfigSpec::default()
+    };
+    match table.get("abstract") {
+        None => {}
+        Some(Value::Boolean(is_abstract)) => spec.is_abstract = *is_abstract,
+        Some(_) => return Err(".abstract should be true or false".to_string()),
+    }
+    match table.get("args") {
+        None => {}
+        Some(Value::Array(args)) => {
+            for arg in args {
+                let arg = arg.as_str().ok_or(".args should only have strings")?;
+                spec.args.push(arg.to_string());
+            }
+        }
+        Some(_) => return Err(".args should be a list of cmake arguments".to_string()),
+    }
+    match table.get("env") {
+        None => {}
+        Some(Value::Table(vars)) => {
+            for (key, value) in vars {
+                let value = value
+                    .as_str()
+                    .ok_or(format!(".env.{} should be a string", key))?;
+                spec.env.push((key.clone(), value.to_string()));
+            }
+        }
+        Some(_) => return Err(".env should be a table".to_string()),
+    }
+    for key in table.keys() {
+        let known = [
+            "extends",
+            "abstract",
+            "compiler",
+            "build_type",
+            "generator",
+            "args",
+            "env",
+        ];
+        if !known.contains(&&key[..]) {
+            return Err(format!(".{} isn't something configurations have", key));
+        }
+    }
+    Ok(spec)
+}
+
+/// The projects a workspace manifest lists, like `projects = ["libfoo", "../app"]`.
+///
+/// Relative paths are relative to the manifest.
+pub fn workspace_projects(path: &Path) -> Result<Vec<PathBuf>, String> {
+    let table = read(path)?.ok_or_else(|| format!("There is no workspace manifest {:?}", path))?;
+    let projects = match table.get("projects") {
+        Some(Value::Array(projects)) => projects,
+        _ => return Err(format!("{:?} should have a list of projects", path)),
+    };
+    let dir = path.parent().unwrap_or(Path::new("."));
+    projects
+        .iter()
+        .map(|project| match project.as_str() {
+            Some(project) => Ok(dir.join(project)),
+            None => Err(format!("The projects in {:?} should be paths", path)),
+        })
+        .collect()
+}
+
+/// A missing file is fine, there is nothing to configure then.
+fn read(path: &Path) -> Result<Option<Table>, String> {
+    let text = match std::fs::read_to_string(path) {
+        Ok(text) => text,
+        Err(ref e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+        Err(e) => return Err(format!("Failed to read {:?}: {}", path, e)),
+    };
+    text.parse()
+        .map(Some)
+        .map_err(|e| format!("Invalid {:?}: {}", path, e))
+}
+
+/// A command, or a list of them.
+fn commands(table: &Table, key: &str) -> Result<Vec<String>, String> {
+    match table.get(key) {
+        None => Ok(Vec::new()),
+        Some(Value::String(command)) => Ok(vec![command.clone()]),
+        Some(Value::Array(commands)) => commands
+            .iter()
+            .map(|c| match c.as_str() {
+                Some(command) => Ok(command.to_string()),
+                None => Err(format!("hooks.{} should only have strings", key)),
+            })
+            .collect(),
+        Some(_) => Err(format!(
+            "hooks.{} should be a command or a list of them",
+            key
+        )),
+    }
+}
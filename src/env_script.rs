@@ -0,0 +1,30 @@
+//! `env.sh` in each configuration directory, the environment to run what it built in, and an
+//! `.envrc` for direnv to load it.
+
+use super::shell_quote;
+use std::path::Path;
+
+/// A PowerShell script on Windows, like `reconfigure.ps1`.
+pub const FILE_NAME: &str = if cfg!(windows) { "env.ps1" } else { "env.sh" };
+
+const ENVRC: &str = ".envrc";
+
+/// Writes the script exporting `vars` to the configuration directory `dir`, named `name`.
+pub fn write(dir: &Path, name: &str, vars: &[(String, String)]) -> Result<(), String> {
+    let mut text = format!("# The environment of {}, written by mkqcb\n", name);
+    for (key, value) in vars {
+        text += &if cfg!(windows) {
+            format!("$env:{} = {}\n", key, super::reconfigure::quote(value))
+        } else {
+            format!("export {}={}\n", key, shell_quote(value))
+        };
+    }
+    let path = dir.join(FILE_NAME);
+    std::fs::write(&path, text).map_err(|e| format!("Failed to write {:?}: {}", path, e))?;
+    if !cfg!(windows) {
+        let path = dir.join(ENVRC);
+        std::fs::write(&path, format!("source_env {}\n", FILE_NAME))
+            .map_err(|e| format!("Failed to write {:?}: {}", path, e))?;
+    }
+    Ok(())
+}
@@ -0,0 +1,127 @@
+//! What cmake says it generated, through the cmake-file-api(7).
+//!
+//! A query in the build directory asks for the codemodel and toolchains, which cmake 3.14 and
+//! newer answer with reply files once configuring is done.
+
+use serde_json::Value;
+use std::path::{Path, PathBuf};
+
+/// The query files, ours so other clients don't see replies they didn't ask for
+const QUERY_DIR: &str = ".cmake/api/v1/query/client-mkqcb";
+const REPLY_DIR: &str = ".cmake/api/v1/reply";
+const CLIENT: &str = "client-mkqcb";
+const OBJECTS: [&str; 2] = ["codemodel-v2", "toolchains-v1"];
+
+pub struct Model {
+    /// Of the first configuration, which is the only one unless the generator is multi-config
+    pub targets: usize,
+    pub toolchains: Vec<Toolchain>,
+}
+
+pub struct Toolchain {
+    pub language: String,
+    /// Like GNU or Clang
+    pub compiler_id: Option<String>,
+    pub compiler_version: Option<String>,
+    pub compiler_path: Option<String>,
+}
+
+impl Toolchain {
+    /// Like `GNU 13.2.0`.
+    pub fn describe(&self) -> String {
+        match (&self.compiler_id, &self.compiler_version) {
+            (Some(id), Some(version)) => format!("{} {}", id, version),
+            (Some(id), None) => id.clone(),
+            _ => "-".to_string(),
+        }
+    }
+}
+
+impl Model {
+    /// The toolchain of C++, or failing that C, which is what a configuration is known by.
+    pub fn main_toolchain(&self) -> Option<&Toolchain> {
+        ["CXX", "C"]
+            .iter()
+            .find_map(|language| self.toolchains.iter().find(|t| t.language == *language))
+            .or_else(|| self.toolchains.first())
+    }
+}
+
+/// Asks cmake to describe what it generates in the build directory `dir`.
+pub fn write_query(dir: &Path) -> Result<(), String> {
+    let query_dir = dir.join(QUERY_DIR);
+    std::fs::create_dir_all(&query_dir)
+        .map_err(|e| format!("Failed to create {:?}: {}", query_dir, e))?;
+    for object in OBJECTS {
+        let path = query_dir.join(object);
+        std::fs::write(&path, "").map_err(|e| format!("Failed to write {:?}: {}", path, e))?;
+    }
+    Ok(())
+}
+
+/// What cmake replied to the query in `dir`, if it replied at all.
+pub fn read_reply(dir: &Path) -> Result<Option<Model>, String> {
+    let reply_dir = dir.join(REPLY_DIR);
+    let index = match latest_index(&reply_dir) {
+        Some(index) => index,
+        None => return Ok(None),
+    };
+    let index = read_json(&index)?;
+    let replies = &index["reply"][CLIENT];
+    let object = |name: &str| -> Result<Value, String> {
+        let reply = &replies[name];
+        if let Some(error) = reply["error"].as_str() {
+            return Err(format!("cmake couldn't describe its {}: {}", name, error));
+        }
+        match reply["jsonFile"].as_str() {
+            Some(file) => read_json(&reply_dir.join(file)),
+            None => Err(format!("cmake didn't reply with its {}", name)),
+        }
+    };
+    let codemodel = object("codemodel-v2")?;
+    let targets = codemodel["configurations"][0]["targets"]
+        .as_array()
+        .map_or(0, |targets| targets.len());
+    // Older cmake knows codemodels but not toolchains
+    let toolchains = match object("toolchains-v1") {
+        Ok(toolchains) => toolchains["toolchains"]
+            .as_array()
+            .map(|list| list.iter().map(toolchain).collect())
+            .unwrap_or_default(),
+        Err(_) => Vec::new(),
+    };
+    Ok(Some(Model {
+        targets,
+        toolchains,
+    }))
+}
+
+fn toolchain(entry: &Value) -> Toolchain {
+    let compiler = &entry["compiler"];
+    let string = |value: &Value| value.as_str().map(|s| s.to_string());
+    Toolchain {
+        language: string(&entry["language"]).unwrap_or_default(),
+        compiler_id: string(&compiler["id"]),
+        compiler_version: string(&compiler["version"]),
+        compiler_path: string(&compiler["path"]),
+    }
+}
+
+/// The newest index file, whose name sorts last.
+fn latest_index(reply_dir: &Path) -> Option<PathBuf> {
+    std::fs::read_dir(reply_dir)
+        .ok()?
+        .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+        .filter(|path| {
+            path.file_name()
+                .and_then(|name| name.to_str())
+                .is_some_and(|name| name.starts_with("index-") && name.ends_with(".json"))
+        })
+        .max()
+}
+
+fn read_json(path: &Path) -> Result<Value, String> {
+    let text =
+        std::fs::read_to_string(path).map_err(|e| format!("Failed to read {:?}: {}", path, e))?;
+    serde_json::from_str(&text).map_err(|e| format!("Invalid {:?}: {}", path, e))
+}
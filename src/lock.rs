@@ -0,0 +1,224 @@
+//! `mkqcb.lock`, which records a build tree so it can be created again.
+
+use super::{BuildSystem, BuildType, Compiler, Config, Settings};
+use serde_json::Value;
+use std::path::{Path, PathBuf};
+
+pub const FILE_NAME: &str = "mkqcb.lock";
+
+pub struct Lock {
+    pub cmake_version: Option<String>,
+    pub configs: Vec<Config>,
+}
+
+impl Lock {
+    pub fn path(build_dir: &Path) -> PathBuf {
+        build_dir.join(FILE_NAME)
+    }
+    /// Records `configs` along with everything they get from `settings`.
+    pub fn new<'a, I: IntoIterator<Item = &'a Config>>(configs: I, settings: &Settings) -> Self {
+        let configs = configs
+            .into_iter()
+            .map(|c| {
+                let mut c = c.clone();
+                c.build_system = Some(c.build_system.unwrap_or(settings.build_system));
+                c.cmake_args.extend(settings.common_args.iter().cloned());
+                c
+            })
+            .collect();
+        Lock {
+            cmake_version: settings.cmake_version.clone(),
+            configs,
+        }
+    }
+    pub fn read(path: &Path) -> Result<Self, String> {
+        let text = std::fs::read_to_string(path)
+            .map_err(|e| format!("Failed to read {:?}: {}", path, e))?;
+        Self::parse(&text).map_err(|e| format!("Invalid lockfile {:?}: {}", path, e))
+    }
+    pub fn write(&self, path: &Path) -> Result<(), String> {
+        std::fs::write(path, self.to_json() + "\n")
+            .map_err(|e| format!("Failed to write {:?}: {}", path, e))
+    }
+    fn to_json(&self) -> String {
+        let configs: Vec<_> = self
+            .configs
+            .iter()
+            .map(|c| {
+                json!({
+                    "name": c.name,
+                    "compiler": compiler_json(&c.compiler),
+                    "build_type": c.build_type.map(|b| b.name()),
+                    "generator": c.build_system.map(|b| b.generator()),
+                    "cmake_args": c.cmake_args,
+                    "env": env_json(&c.env),
+                })
+            })
+            .collect();
+        let lock = json!({
+            "mkqcb_version": env!("CARGO_PKG_VERSION"),
+            "cmake_version": self.cmake_version,
+            "configurations": configs,
+        });
+        serde_json::to_string_pretty(&lock).unwrap()
+    }
+    fn parse(text: &str) -> Result<Self, String> {
+        let lock: Value = serde_json::from_str(text).map_err(|e| e.to_string())?;
+        let mut configs = Vec::new();
+        let entries = lock["configurations"]
+            .as_array()
+            .ok_or("missing configurations")?;
+        for entry in entries {
+            let name = string(entry, "name")?;
+            let build_system = match entry["generator"].as_str() {
+                Some(generator) => match BuildSystem::from_generator(generator) {
+                    Some(build_system) => Some(build_system),
+                    None => return Err(format!("unsupported generator {:?}", generator)),
+                },
+                None => None,
+            };
+            let build_type = match entry["build_type"].as_str() {
+                Some(name) => Some(BuildType::from_name(name)?),
+                None => None,
+            };
+            let mut env = Vec::new();
+            if let Some(vars) = entry["env"].as_object() {
+                for (key, value) in vars {
+                    let value = value.as_str().ok_or("expected a string")?;
+                    env.push((key.clone(), value.to_string()));
+                }
+            }
+            configs.push(Config {
+                compiler: compiler_from_json(&entry["compiler"])?,
+                build_type,
+                cmake_args: strings(&entry["cmake_args"])?,
+                build_system,
+                env,
+                name,
+            });
+        }
+        Ok(Lock {
+            cmake_version: lock["cmake_version"].as_str().map(|s| s.to_string()),
+            configs,
+        })
+    }
+}
+
+pub fn env_json(env: &[(String, String)]) -> Value {
+    let vars = env.iter().map(|(k, v)| (k.clone(), Value::from(&v[..])));
+    Value::Object(vars.collect())
+}
+
+fn string(value: &Value, key: &str) -> Result<String, String> {
+    match value[key].as_str() {
+        Some(s) => Ok(s.to_string()),
+        None => Err(format!("missing {}", key)),
+    }
+}
+
+fn strings(value: &Value) -> Result<Vec<String>, String> {
+    let array = value.as_array().ok_or("expected a list of strings")?;
+    array
+        .iter()
+        .map(|v| match v.as_str() {
+            Some(s) => Ok(s.to_string()),
+            None => Err("expected a list of strings".to_string()),
+        })
+        .collect()
+}
+
+fn compiler_json(compiler: &Compiler) -> Value {
+    match *compiler {
+        Compiler::Gcc => json!({"kind": "gcc"}),
+        Compiler::Clang => json!({"kind": "clang"}),
+        Compiler::Toolchain { ref name, ref file } => {
+            json!({"kind": "toolchain", "name": name, "file": file})
+        }
+        Compiler::Custom {
+            ref name,
+            ref c,
+            ref cxx,
+        } => json!({"kind": "custom", "name": name, "c": c, "cxx": cxx}),
+    }
+}
+
+fn compiler_from_json(value: &Value) -> Result<Compiler, String> {
+    let optional = |key| value[key].as_str().map(|s: &str| s.to_string());
+    match value["kind"].as_str() {
+        Some("gcc") => Ok(Compiler::Gcc),
+        Some("clang") => Ok(Compiler::Clang),
+        Some("toolchain") => Ok(Compiler::Toolchain {
+            name: string(value, "name")?,
+            file: PathBuf::from(string(value, "file")?),
+        }),
+        Some("custom") => Ok(Compiler::Custom {
+            name: string(value, "name")?,
+            c: optional("c"),
+            cxx: optional("cxx"),
+        }),
+        _ => Err("unknown compiler".to_string()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn lock() -> Lock {
+        let config = |name: &str, compiler| Config {
+            name: name.to_string(),
+            compiler,
+            build_type: None,
+            cmake_args: Vec::new(),
+            build_system: None,
+            env: Vec::new(),
+        };
+        let mut custom = config(
+            "Cross-Release",
+            Compiler::Toolchain {
+                name: "Cross".to_string(),
+                file: PathBuf::from("/opt/cross.cmake"),
+            },
+        );
+        custom.build_type = Some(BuildType::Release);
+        custom.build_system = Some(BuildSystem::Ninja);
+        custom.cmake_args = vec!["-DSANITIZE=address".to_string(), "-DA=b c".to_string()];
+        custom.env = vec![("CFLAGS".to_string(), "-O1".to_string())];
+        Lock {
+            cmake_version: Some("3.28.1".to_string()),
+            configs: vec![
+                config("GCC", Compiler::Gcc),
+                custom,
+                config(
+                    "Icc-Debug",
+                    Compiler::Custom {
+                        name: "Icc".to_string(),
+                        c: Some("icc".to_string()),
+                        cxx: None,
+                    },
+                ),
+            ],
+        }
+    }
+
+    #[test]
+    fn round_trip() {
+        let json = lock().to_json();
+        let parsed = Lock::parse(&json).unwrap();
+        assert_eq!(parsed.configs.len(), 3);
+        assert_eq!(parsed.cmake_version.as_deref(), Some("3.28.1"));
+        assert_eq!(parsed.to_json(), json);
+    }
+
+    #[test]
+    fn invalid_locks() {
+        assert!(Lock::parse("{}").is_err());
+        assert!(Lock::parse("not json").is_err());
+        let unknown = r#"{"configurations": [{"name": "A", "compiler": {"kind": "msvc"}}]}"#;
+        assert!(Lock::parse(unknown).is_err());
+        let generator = r#"{"configurations": [
+            {"name": "A", "compiler": {"kind": "gcc"}, "cmake_args": [], "generator": "Nope"}
+        ]}"#;
+        assert!(Lock::parse(generator).is_err());
+    }
+}
@@ -0,0 +1,56 @@
+//! What gets said beyond the usual progress, depending on how many `-v` there are.
+//!
+//! With `-v` it's the commands that get run and where, with `-vv` also why mkqcb decided what it
+//! did about the project and the machine.
+
+use super::{command_line, paint, progress_output, shell_quote};
+use ansi_term::Style;
+use std::io::Write;
+use std::process::Command;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+static VERBOSITY: AtomicUsize = AtomicUsize::new(0);
+
+/// Sets the verbosity, the number of times `-v` was given.
+pub fn set_verbosity(verbosity: usize) {
+    VERBOSITY.store(verbosity, Ordering::Relaxed);
+}
+
+fn enabled(verbosity: usize) -> bool {
+    VERBOSITY.load(Ordering::Relaxed) >= verbosity
+}
+
+fn say(msg: &str) {
+    let _ = writeln!(progress_output(), "{}", paint(Style::new().dimmed(), msg));
+}
+
+pub fn warn(msg: &str) {
+    use ansi_term::Colour::Yellow;
+    eprintln!("{} {}", paint(Yellow.bold(), "warning:"), msg);
+}
+
+/// For `-v`.
+pub fn info(msg: &str) {
+    if enabled(1) {
+        say(msg);
+    }
+}
+
+/// For `-vv`, the decisions that are otherwise made quietly.
+pub fn debug(msg: &str) {
+    if enabled(2) {
+        say(msg);
+    }
+}
+
+/// Shows `cmd` as it would be typed into a shell, with `-v`.
+pub fn command(cmd: &Command) {
+    if !enabled(1) {
+        return;
+    }
+    let mut line = String::new();
+    if let Some(dir) = cmd.get_current_dir() {
+        line += &format!("cd {} && ", shell_quote(&dir.to_string_lossy()));
+    }
+    say(&format!("$ {}{}", line, command_line(cmd)));
+}
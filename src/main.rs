@@ -1,26 +1,97 @@
+extern crate ctrlc;
 extern crate getopts;
+#[macro_use]
+extern crate serde_json;
+extern crate toml;
 
-use getopts::Options;
+mod cache;
+mod cmakelists;
+mod completions;
+mod conan;
+mod config_file;
+mod env_script;
+mod fileapi;
+mod lock;
+mod log;
+mod presets;
+mod probe;
+mod qtcreator;
+mod reconfigure;
+mod sanitizers;
+mod stats;
+mod time_trace;
+mod vcpkg;
+
+use cache::CMakeCache;
+use completions::Options;
+use config_file::{ConfigFile, ConfigSpec};
+use getopts::Matches;
+use lock::Lock;
+use log::warn;
+use stats::{Stats, Timing};
+use std::ffi::{OsStr, OsString};
+use std::io::Write;
 use std::path::{Path, PathBuf};
+use std::process::{Child, Command, ExitStatus, Stdio};
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::time::{Duration, Instant};
 
 #[derive(Clone, Copy)]
 enum BuildSystem {
     Make,
+    /// The Makefiles of mingw32-make, with the shell of Windows
+    MinGWMake,
     Ninja,
+    /// An Xcode project, with every build type in it
+    Xcode,
 }
 
 impl BuildSystem {
+    /// Plain make, which is MinGW's on Windows, with MSYS2 as well.
+    fn make() -> BuildSystem {
+        if cfg!(windows) {
+            BuildSystem::MinGWMake
+        } else {
+            BuildSystem::Make
+        }
+    }
+    fn from_generator(generator: &str) -> Option<BuildSystem> {
+        match generator {
+            "Ninja" | "CodeBlocks - Ninja" => Some(BuildSystem::Ninja),
+            "Unix Makefiles" | "CodeBlocks - Unix Makefiles" => Some(BuildSystem::Make),
+            "MinGW Makefiles" | "CodeBlocks - MinGW Makefiles" => Some(BuildSystem::MinGWMake),
+            "Xcode" => Some(BuildSystem::Xcode),
+            _ => None,
+        }
+    }
     fn as_cmake_arg(&self) -> &'static str {
         match *self {
             BuildSystem::Make => "-GCodeBlocks - Unix Makefiles",
+            BuildSystem::MinGWMake => "-GCodeBlocks - MinGW Makefiles",
             BuildSystem::Ninja => "-GCodeBlocks - Ninja",
+            BuildSystem::Xcode => "-GXcode",
         }
     }
+    fn generator(&self) -> &'static str {
+        &self.as_cmake_arg()[2..]
+    }
 }
 
+#[derive(Clone)]
 enum Compiler {
     Gcc,
     Clang,
+    /// Whatever the toolchain file sets up, usually a cross compiler
+    Toolchain {
+        name: String,
+        file: PathBuf,
+    },
+    /// Picked up from the CC/CXX environment variables
+    Custom {
+        name: String,
+        c: Option<String>,
+        cxx: Option<String>,
+    },
 }
 
 use std::fmt::{Display, Formatter};
@@ -33,20 +104,97 @@ impl Display for Compiler {
             match *self {
                 Gcc => "GCC",
                 Clang => "Clang",
+                Toolchain { ref name, .. } | Custom { ref name, .. } => name,
             }
         )
     }
 }
 
 impl Compiler {
-    fn as_cmake_args(&self) -> [&'static str; 2] {
+    fn as_cmake_args(&self) -> Vec<String> {
         match *self {
-            Gcc => ["-DCMAKE_C_COMPILER=gcc", "-DCMAKE_CXX_COMPILER=g++"],
-            Clang => ["-DCMAKE_C_COMPILER=clang", "-DCMAKE_CXX_COMPILER=clang++"],
+            Gcc => vec![
+                "-DCMAKE_C_COMPILER=gcc".to_string(),
+                "-DCMAKE_CXX_COMPILER=g++".to_string(),
+            ],
+            Clang => vec![
+                "-DCMAKE_C_COMPILER=clang".to_string(),
+                "-DCMAKE_CXX_COMPILER=clang++".to_string(),
+            ],
+            Toolchain { ref file, .. } => {
+                vec![format!("-DCMAKE_TOOLCHAIN_FILE={}", file.display())]
+            }
+            Custom { ref c, ref cxx, .. } => {
+                let mut args = Vec::new();
+                if let Some(ref c) = *c {
+                    args.push(format!("-DCMAKE_C_COMPILER={}", c));
+                }
+                if let Some(ref cxx) = *cxx {
+                    args.push(format!("-DCMAKE_CXX_COMPILER={}", cxx));
+                }
+                args
+            }
+        }
+    }
+    /// The compiler requested with CC/CXX, unless it's one we create configurations for anyway.
+    fn from_env() -> Option<Compiler> {
+        use std::env;
+        let c = env::var("CC").ok().filter(|s| !s.is_empty());
+        let cxx = env::var("CXX").ok().filter(|s| !s.is_empty());
+        let name = program_name(c.as_ref().or(cxx.as_ref())?);
+        let builtin = ["gcc", "g++", "clang", "clang++", "cc", "c++"];
+        let is_builtin = |program: &Option<String>| match *program {
+            Some(ref program) => builtin.contains(&&program_name(program)[..]),
+            None => true,
+        };
+        if is_builtin(&c) && is_builtin(&cxx) {
+            log::debug("CC and CXX name compilers there are configurations for anyway");
+            return None;
+        }
+        log::debug(&format!(
+            "Adding configurations for {} from CC and CXX",
+            name
+        ));
+        Some(Custom { name, c, cxx })
+    }
+    /// Parses `--compiler`: gcc, clang, a toolchain file, a C compiler, or a `CC,CXX` pair.
+    fn from_name(name: &str) -> Result<Compiler, String> {
+        match name {
+            "gcc" | "GCC" => return Ok(Gcc),
+            "clang" | "Clang" => return Ok(Clang),
+            _ if name.ends_with(".cmake") => return Compiler::toolchain(name),
+            _ => {}
         }
+        let (c, cxx) = if let Some(pos) = name.find(',') {
+            (name[..pos].to_string(), name[pos + 1..].to_string())
+        } else if name.contains("clang") {
+            (name.to_string(), name.replacen("clang", "clang++", 1))
+        } else if name.contains("gcc") {
+            (name.to_string(), name.replacen("gcc", "g++", 1))
+        } else {
+            return Err(format!(
+                "Can't tell the C++ compiler for {:?}, pass CC,CXX",
+                name
+            ));
+        };
+        Ok(Custom {
+            name: file_name(Path::new(&c)),
+            c: Some(c),
+            cxx: Some(cxx),
+        })
+    }
+    fn toolchain(path: &str) -> Result<Compiler, String> {
+        let file = canonicalize(path)
+            .map_err(|e| format!("Failed to find toolchain file {:?}: {}", path, e))?;
+        let name = match file.file_stem() {
+            Some(stem) => stem.to_string_lossy().into_owned(),
+            None => return Err(format!("Invalid toolchain file name: {:?}", path)),
+        };
+        Ok(Toolchain { name, file })
     }
 }
 
+#[derive(Clone, Copy)]
 enum BuildType {
     Debug,
     Release,
@@ -59,170 +207,5170 @@ impl BuildType {
             Release => "-DCMAKE_BUILD_TYPE=Release",
         }
     }
+    fn name(&self) -> &'static str {
+        &self.as_cmake_arg()["-DCMAKE_BUILD_TYPE=".len()..]
+    }
+    fn from_name(name: &str) -> Result<BuildType, String> {
+        match &name.to_lowercase()[..] {
+            "debug" => Ok(Debug),
+            "release" => Ok(Release),
+            _ => Err(format!("Unknown build type: {}", name)),
+        }
+    }
 }
 
 use Compiler::*;
 use BuildType::*;
 
+#[derive(Clone, Copy)]
+enum Launcher {
+    Distcc,
+    Icecc,
+}
+
+impl Launcher {
+    fn from_name(name: &str) -> Option<Launcher> {
+        match name {
+            "distcc" => Some(Launcher::Distcc),
+            "icecc" | "icecream" => Some(Launcher::Icecc),
+            _ => None,
+        }
+    }
+    fn as_cmake_args(&self) -> [&'static str; 2] {
+        match *self {
+            Launcher::Distcc => [
+                "-DCMAKE_C_COMPILER_LAUNCHER=distcc",
+                "-DCMAKE_CXX_COMPILER_LAUNCHER=distcc",
+            ],
+            Launcher::Icecc => [
+                "-DCMAKE_C_COMPILER_LAUNCHER=icecc",
+                "-DCMAKE_CXX_COMPILER_LAUNCHER=icecc",
+            ],
+        }
+    }
+    /// How many compile jobs the farm can take, as far as we can tell.
+    fn default_jobs(&self) -> usize {
+        match *self {
+            Launcher::Distcc => {
+                let jobs = Command::new("distcc")
+                    .arg("-j")
+                    .output()
+                    .ok()
+                    .and_then(|out| String::from_utf8(out.stdout).ok())
+                    .and_then(|s| s.trim().parse().ok());
+                jobs.unwrap_or_else(|| local_jobs() * 2)
+            }
+            // icecream has no way to ask the scheduler, so just assume a decent farm
+            Launcher::Icecc => local_jobs() * 4,
+        }
+    }
+}
+
+fn local_jobs() -> usize {
+    std::thread::available_parallelism()
+        .map(|n| n.get())
+        .unwrap_or(1)
+}
+
+/// The Ninja job pools of every configuration, by name, from `--job-pool` and `--link-jobs`.
+///
+/// With a launcher, compile jobs go to the farm, but linking still happens locally, so the two
+/// get separate pools unless asked otherwise.
+fn job_pools(
+    matches: &Matches,
+    launcher: Option<(Launcher, usize)>,
+) -> Result<Vec<(String, usize)>, String> {
+    let mut pools = Vec::new();
+    if let Some((_, jobs)) = launcher {
+        pools.push(("compile".to_string(), jobs));
+        pools.push(("link".to_string(), local_jobs()));
+    }
+    let mut declared = Vec::new();
+    for pool in matches.opt_strs("job-pool") {
+        match pool.split_once('=') {
+            Some((name, jobs)) if !name.is_empty() && !name.contains(';') => {
+                declared.push((name.to_string(), jobs.to_string()))
+            }
+            _ => return Err(format!("Invalid --job-pool {:?}, expected NAME=N", pool)),
+        }
+    }
+    if let Some(jobs) = matches.opt_str("link-jobs") {
+        declared.push(("link".to_string(), jobs));
+    }
+    for (name, jobs) in declared {
+        let jobs = match jobs.parse() {
+            Ok(0) | Err(_) => return Err(format!("Invalid number of jobs for {}: {}", name, jobs)),
+            Ok(jobs) => jobs,
+        };
+        pools.retain(|(n, _)| *n != name);
+        pools.push((name, jobs));
+    }
+    Ok(pools)
+}
+
+/// Cache arguments that declare `pools`, with compiling and linking going to those named so.
+fn job_pool_args(pools: &[(String, usize)]) -> Vec<String> {
+    if pools.is_empty() {
+        return Vec::new();
+    }
+    let declared: Vec<String> = pools
+        .iter()
+        .map(|(name, jobs)| format!("{}={}", name, jobs))
+        .collect();
+    let mut args = vec![format!("-DCMAKE_JOB_POOLS={}", declared.join(";"))];
+    for (name, var) in [("compile", "COMPILE"), ("link", "LINK")] {
+        if pools.iter().any(|(n, _)| n == name) {
+            args.push(format!("-DCMAKE_JOB_POOL_{}={}", var, name));
+        }
+    }
+    args
+}
+
+#[derive(Clone)]
 struct Config {
     name: String,
     compiler: Compiler,
-    build_type: BuildType,
-    cmake_args: Vec<&'static str>,
+    /// Left to the project when unset
+    build_type: Option<BuildType>,
+    cmake_args: Vec<String>,
+    /// Overrides the build system chosen on the command line
+    build_system: Option<BuildSystem>,
+    /// Environment variables cmake runs with
+    env: Vec<(String, String)>,
 }
 
 fn config(name: &str, comp: Compiler, build_type: BuildType, args: &[&'static str]) -> Config {
     Config {
         name: format!("{}-{}", comp, name),
         compiler: comp,
-        build_type: build_type,
-        cmake_args: args.to_owned(),
+        build_type: Some(build_type),
+        cmake_args: args.iter().map(|s| s.to_string()).collect(),
+        build_system: None,
+        env: Vec::new(),
     }
 }
 
-fn create_config(conf: &Config, build_system: BuildSystem, project_dir: &str) -> bool {
-    use std::{fs, env};
-    use std::process::Command;
-    let parent_dir = env::current_dir().unwrap();
-    fs::create_dir(&conf.name).unwrap();
-    env::set_current_dir(&Path::new(&conf.name)).unwrap();
-    let result = Command::new("cmake")
-        .arg(project_dir)
-        .arg(build_system.as_cmake_arg())
-        .args(&conf.compiler.as_cmake_args())
-        .arg(conf.build_type.as_cmake_arg())
-        .args(&conf.cmake_args)
-        .status()
-        .unwrap();
-    env::set_current_dir(&parent_dir).unwrap();
-    result.success()
+fn find_in_path(program: &str) -> Option<PathBuf> {
+    let path = std::env::var_os("PATH")?;
+    // Windows runs gcc.exe for gcc
+    let exe =
+        Some(format!("{}{}", program, std::env::consts::EXE_SUFFIX)).filter(|exe| exe != program);
+    std::env::split_paths(&path)
+        .flat_map(|dir| {
+            let mut candidates = vec![dir.join(program)];
+            candidates.extend(exe.iter().map(|exe| dir.join(exe)));
+            candidates
+        })
+        .find(|candidate| candidate.is_file())
 }
 
-extern crate ansi_term;
+/// Whether `program` is there to run, as a path or a name to find in the PATH.
+fn installed(program: &str) -> bool {
+    if Path::new(program).components().count() > 1 {
+        Path::new(program).is_file()
+    } else {
+        find_in_path(program).is_some()
+    }
+}
 
-fn print_usage(program: &str, opts: &Options) {
-    let brief = format!("Usage: {} project_dir [options]", program);
-    print!("{}", opts.usage(&brief));
+/// The name of the program at `path`, without the `.exe` it has on Windows.
+fn program_name(path: &str) -> String {
+    let name = file_name(Path::new(path));
+    match name.strip_suffix(std::env::consts::EXE_SUFFIX) {
+        Some(stem) if !stem.is_empty() => stem.to_string(),
+        _ => name,
+    }
 }
 
-struct CMakeListsProperties {
-    has_sanitize: bool,
+/// `std::fs::canonicalize`, without the `\\?\` Windows puts in front, which cmake doesn't take.
+fn canonicalize<P: AsRef<Path>>(path: P) -> std::io::Result<PathBuf> {
+    let path = std::fs::canonicalize(path)?;
+    if cfg!(windows) {
+        let text = path.to_string_lossy().into_owned();
+        // Network shares are \\?\UNC\server\share, which need it
+        if let Some(rest) = text
+            .strip_prefix(r"\\?\")
+            .filter(|rest| !rest.starts_with("UNC"))
+        {
+            return Ok(PathBuf::from(rest));
+        }
+    }
+    Ok(path)
 }
 
-fn parse_cmakelists_txt(path: &Path) -> std::io::Result<CMakeListsProperties> {
-    use std::fs::File;
-    use std::io::Read;
-    let mut f = File::open(path.join("CMakeLists.txt"))?;
-    let mut s = String::new();
-    f.read_to_string(&mut s)?;
-    let has_sanitize = match s.find("${SANITIZE}") {
-        Some(_) => true,
-        None => false,
+/// `path` as it goes into a CMake script, where backslashes would be escapes.
+fn cmake_path(path: &Path) -> String {
+    let text = path.display().to_string();
+    if cfg!(windows) {
+        text.replace('\\', "/")
+    } else {
+        text
+    }
+}
+
+fn find_emscripten_toolchain() -> Option<PathBuf> {
+    use std::env;
+    let module = Path::new("cmake/Modules/Platform/Emscripten.cmake");
+    let mut roots = Vec::new();
+    if let Some(dir) = env::var_os("EMSCRIPTEN") {
+        roots.push(PathBuf::from(dir));
+    }
+    if let Some(dir) = env::var_os("EMSDK") {
+        roots.push(Path::new(&dir).join("upstream/emscripten"));
+    }
+    // emcmake lives in the emscripten root, but is often symlinked onto the PATH
+    if let Some(emcmake) = find_in_path("emcmake") {
+        if let Some(dir) = canonicalize(emcmake)
+            .ok()
+            .and_then(|p| p.parent().map(|p| p.to_owned()))
+        {
+            roots.push(dir);
+        }
+    }
+    log::debug(&format!("Looking for {} in {:?}", module.display(), roots));
+    roots
+        .into_iter()
+        .map(|root| root.join(module))
+        .find(|file| file.is_file())
+}
+
+/// Whether `name` matches any of the comma separated `patterns`.
+fn matches_patterns(patterns: &str, name: &str) -> bool {
+    patterns.split(',').any(|p| matches_pattern(p, name))
+}
+
+/// Simple glob matching for configuration names, where `*` matches anything.
+fn matches_pattern(pattern: &str, name: &str) -> bool {
+    let mut parts = pattern.split('*');
+    let first = parts.next().unwrap_or("");
+    if !name.starts_with(first) {
+        return false;
+    }
+    let mut rest = &name[first.len()..];
+    let parts: Vec<&str> = parts.collect();
+    match parts.split_last() {
+        None => rest.is_empty(),
+        Some((last, middle)) => {
+            for part in middle {
+                match rest.find(part) {
+                    Some(pos) => rest = &rest[pos + part.len()..],
+                    None => return false,
+                }
+            }
+            rest.ends_with(last)
+        }
+    }
+}
+
+/// The kinds of configurations that only get created when asked for by name, like with `*-Tidy`.
+fn opt_in_kinds() -> Vec<&'static str> {
+    let mut kinds = vec!["Bench", "Valgrind"];
+    kinds.extend(ANALYZERS.iter().map(|a| a.name));
+    kinds
+}
+
+/// Whether any of the comma separated `patterns` matches the opt-in configuration `name` with
+/// its kind spelled out.
+fn names_opt_in(patterns: &str, name: &str) -> bool {
+    let kind = name.rsplit('-').next().unwrap_or(name);
+    patterns
+        .split(',')
+        .any(|p| p.contains(kind) && matches_pattern(p, name))
+}
+
+/// Patterns matching every configuration there is, opt-in ones included.
+fn everything_patterns() -> String {
+    let kinds = opt_in_kinds()
+        .into_iter()
+        .map(|kind| format!(",*-{}", kind));
+    std::iter::once("*".to_string()).chain(kinds).collect()
+}
+
+/// The profiles there are unless a config file says otherwise, with their `--only` patterns.
+const PROFILES: [(&str, &str); 6] = [
+    ("quick", "GCC-Debug"),
+    ("full", "*"),
+    ("ci", "*-Release,*-Asan,*-Ubsan"),
+    ("analyze", "*-Tidy,*-Iwyu,*-Cppcheck"),
+    ("bench", "*-Bench"),
+    ("dynamic", "*-Asan,*-Ubsan,*-Tsan,*-Msan,*-Valgrind"),
+];
+
+/// The patterns of `--only`, or of the profile `--profile` names.
+fn only_patterns(
+    matches: &Matches,
+    profiles: &[(String, String)],
+) -> Result<Option<String>, String> {
+    let name = match (matches.opt_str("only"), matches.opt_str("profile")) {
+        (only, None) => return Ok(only),
+        (Some(_), Some(_)) => return Err("--only and --profile don't go together".to_string()),
+        (None, Some(name)) => name,
     };
-    Ok(CMakeListsProperties { has_sanitize: has_sanitize })
+    if let Some((_, patterns)) = profiles.iter().find(|(n, _)| *n == name) {
+        return Ok(Some(patterns.clone()));
+    }
+    match PROFILES.iter().find(|&&(n, _)| n == name) {
+        Some(&(_, patterns)) => Ok(Some(patterns.to_string())),
+        None => {
+            let mut names: Vec<&str> = PROFILES.iter().map(|&(n, _)| n).collect();
+            for (n, _) in profiles {
+                if !names.contains(&&n[..]) {
+                    names.push(n);
+                }
+            }
+            Err(format!(
+                "Unknown profile: {} (there are {})",
+                name,
+                names.join(", ")
+            ))
+        }
+    }
 }
 
-fn run() -> (i32, Option<String>) {
-    let mut args = std::env::args();
-    let mut opts = Options::new();
-    let program = args.next().unwrap().clone();
-    opts.optflag("", "no-sanitize", "Don't build sanitize configurations");
-    opts.optflag(
-        "",
-        "no-ninja",
-        "Don't use ninja as a build system. Use plain make instead.",
+struct Analyzer {
+    /// Of its configuration
+    name: &'static str,
+    program: &'static str,
+    /// What cmake calls it, in `CMAKE_<LANG>_<variable>`
+    variable: &'static str,
+}
+
+/// Tools cmake can run along with the compiler, each getting a configuration when asked for.
+const ANALYZERS: [Analyzer; 3] = [
+    Analyzer {
+        name: "Tidy",
+        program: "clang-tidy",
+        variable: "CLANG_TIDY",
+    },
+    Analyzer {
+        name: "Iwyu",
+        program: "include-what-you-use",
+        variable: "INCLUDE_WHAT_YOU_USE",
+    },
+    Analyzer {
+        name: "Cppcheck",
+        program: "cppcheck",
+        variable: "CPPCHECK",
+    },
+];
+
+fn analyzer_configs() -> Vec<Config> {
+    ANALYZERS
+        .iter()
+        .map(|a| {
+            let mut conf = config(a.name, Clang, Debug, &[]);
+            for language in ["C", "CXX"] {
+                conf.cmake_args
+                    .push(format!("-DCMAKE_{}_{}={}", language, a.variable, a.program));
+            }
+            conf
+        })
+        .collect()
+}
+
+/// A Bench configuration for the compiler of every plain Release configuration.
+///
+/// That's Release with frame pointers, so benchmarks and perf get whole stacks.
+fn bench_configs(configs: &[Config], without_tests: bool) -> Vec<Config> {
+    let flags = "-DNDEBUG -fno-omit-frame-pointer";
+    configs
+        .iter()
+        .filter(|c| c.name.ends_with("-Release") && c.cmake_args.is_empty())
+        .filter(|c| c.build_system.is_none())
+        // Benchmarks are for running here
+        .filter(|c| !matches!(c.compiler, Toolchain { .. }))
+        .map(|c| {
+            let mut conf = config("Bench", c.compiler.clone(), Release, &[]);
+            for language in ["C", "CXX"] {
+                conf.cmake_args
+                    .push(format!("-DCMAKE_{}_FLAGS={}", language, flags));
+            }
+            if without_tests {
+                conf.cmake_args.push("-DBUILD_TESTING=OFF".to_string());
+            }
+            conf
+        })
+        .collect()
+}
+
+/// A configuration to run under Valgrind, which has no use for sanitizers and needs debug info.
+///
+/// `-O1` keeps it bearable, Valgrind being slow enough already. Projects with an option for
+/// Valgrind, like the client requests of `VALGRIND`, get it turned on.
+fn valgrind_config(props: &CMakeListsProperties) -> Config {
+    let mut conf = config("Valgrind", Clang, Debug, &[]);
+    for language in ["C", "CXX"] {
+        conf.cmake_args
+            .push(format!("-DCMAKE_{}_FLAGS_DEBUG=-O1 -g", language));
+    }
+    let option = props.options.iter().find(|o| {
+        o.name
+            .to_uppercase()
+            .split(|c: char| !c.is_ascii_alphanumeric())
+            .any(|word| word == "VALGRIND")
+    });
+    if let Some(option) = option {
+        log::debug(&format!("Valgrind gets turned on with {}", option.name));
+        conf.cmake_args.push(format!("-D{}:BOOL=ON", option.name));
+    }
+    conf
+}
+
+fn policy_arg(policy: &str) -> Result<String, String> {
+    let mut split = policy.splitn(2, '=');
+    let id = split.next().unwrap_or("");
+    let value = split.next().unwrap_or("");
+    let valid_id =
+        id.len() == 7 && id.starts_with("CMP") && id[3..].chars().all(|c| c.is_ascii_digit());
+    if !valid_id || (value != "NEW" && value != "OLD") {
+        return Err(format!(
+            "Invalid policy setting {:?}, expected something like CMP0077=NEW",
+            policy
+        ));
+    }
+    Ok(format!("-DCMAKE_POLICY_DEFAULT_{}={}", id, value))
+}
+
+const ANDROID_ABIS: [&str; 4] = ["armeabi-v7a", "arm64-v8a", "x86", "x86_64"];
+
+fn android_configs(
+    ndk: &str,
+    abis: Option<&str>,
+    platform: Option<&str>,
+) -> Result<Vec<Config>, String> {
+    let file = Path::new(ndk).join("build/cmake/android.toolchain.cmake");
+    let file = canonicalize(&file).map_err(|e| {
+        format!(
+            "{:?} doesn't look like an Android NDK ({:?}: {})",
+            ndk, file, e
+        )
+    })?;
+    // The toolchain file wants "android-<level>", but a bare level is easier to type
+    let platform = platform.map(|p| {
+        if p.chars().all(|c| c.is_ascii_digit()) {
+            format!("android-{}", p)
+        } else {
+            p.to_string()
+        }
+    });
+    let mut configs = Vec::new();
+    for abi in abis.unwrap_or("arm64-v8a").split(',') {
+        if !ANDROID_ABIS.contains(&abi) {
+            return Err(format!(
+                "Unknown Android ABI {:?}, expected one of {}",
+                abi,
+                ANDROID_ABIS.join(", ")
+            ));
+        }
+        let toolchain = Toolchain {
+            name: format!("Android-{}", abi),
+            file: file.clone(),
+        };
+        for &(name, build_type) in &[("Debug", Debug), ("Release", Release)] {
+            let mut conf = config(name, toolchain.clone(), build_type, &[]);
+            conf.cmake_args.push(format!("-DANDROID_ABI={}", abi));
+            if let Some(ref platform) = platform {
+                conf.cmake_args
+                    .push(format!("-DANDROID_PLATFORM={}", platform));
+            }
+            configs.push(conf);
+        }
+    }
+    Ok(configs)
+}
+
+fn define_arg(define: &str) -> Result<String, String> {
+    match define.find('=') {
+        Some(pos) if pos > 0 => Ok(format!("-D{}", define)),
+        _ => Err(format!(
+            "Invalid cache variable {:?}, expected KEY=VALUE",
+            define
+        )),
+    }
+}
+
+/// Parses the `PATTERN:KEY=VALUE` argument of `--D-for`.
+fn targeted_define_arg(arg: &str) -> Result<(String, String), String> {
+    match arg.find(':') {
+        Some(pos) => Ok((arg[..pos].to_string(), define_arg(&arg[pos + 1..])?)),
+        None => Err(format!(
+            "Invalid --D-for argument {:?}, expected PATTERN:KEY=VALUE",
+            arg
+        )),
+    }
+}
+
+const STRICT_CMAKE_ARGS: [&str; 3] = ["-Wdev", "-Wdeprecated", "--warn-uninitialized"];
+
+/// An image to run cmake in, for toolchains that aren't installed locally.
+struct Container {
+    /// docker, podman, or anything with the same command line
+    engine: String,
+    image: String,
+}
+
+/// A machine to configure on over ssh, and the directory there that mirrors the current one.
+struct Remote {
+    host: String,
+    path: PathBuf,
+}
+
+impl Remote {
+    /// Parses `--remote user@host:/path`.
+    fn parse(spec: &str) -> Result<Remote, String> {
+        match spec.find(':') {
+            Some(pos) if pos > 0 && spec[pos + 1..].starts_with('/') => Ok(Remote {
+                host: spec[..pos].to_string(),
+                path: PathBuf::from(&spec[pos + 1..]),
+            }),
+            _ => Err(format!(
+                "--remote expects user@host:/absolute/path, not {:?}",
+                spec
+            )),
+        }
+    }
+    /// Where the source of `proj_dir` gets copied to.
+    fn project_dir(&self, proj_dir: &Path) -> PathBuf {
+        self.path.join(file_name(proj_dir))
+    }
+    /// The place on the remote of a directory here, relative to the current one.
+    fn dir(&self, dir: &Path) -> PathBuf {
+        self.path.join(dir)
+    }
+    /// Mirrors the project directory to the remote.
+    fn sync(&self, proj_dir: &Path) -> Result<(), String> {
+        let mkdir = Command::new("ssh")
+            .arg(&self.host)
+            .arg(format!(
+                "mkdir -p {}",
+                shell_quote(&self.path.to_string_lossy())
+            ))
+            .status()
+            .map_err(|e| format!("Failed to run ssh: {}", e))?;
+        if !mkdir.success() {
+            return Err(format!("Failed to create {:?} on {}", self.path, self.host));
+        }
+        let mut source = proj_dir.as_os_str().to_os_string();
+        source.push("/");
+        let destination = format!("{}:{}/", self.host, self.project_dir(proj_dir).display());
+        let status = Command::new("rsync")
+            .args(["-az", "--delete"])
+            .arg(source)
+            .arg(&destination)
+            .stdout(child_stdout())
+            .status()
+            .map_err(|e| format!("Failed to run rsync: {}", e))?;
+        if !status.success() {
+            return Err(format!("Failed to copy {:?} to {}", proj_dir, destination));
+        }
+        Ok(())
+    }
+}
+
+/// Settings that apply to every configuration in a run
+struct Settings {
+    build_system: BuildSystem,
+    common_args: Vec<String>,
+    launcher_jobs: Option<usize>,
+    cmake: String,
+    build: bool,
+    build_target: Option<String>,
+    build_jobs: Option<usize>,
+    test: bool,
+    /// Build and test through the Experimental steps of ctest, which submit the results to CDash
+    dashboard: bool,
+    ctest: PathBuf,
+    /// How many configurations to create at once
+    jobs: usize,
+    /// Log cmake output to a file instead of the terminal
+    quiet: bool,
+    /// Print a JSON report on stdout
+    json: bool,
+    /// Carry on with the remaining configurations after a failure
+    keep_going: bool,
+    /// Leave the directories of configurations that failed to configure
+    keep_failed: bool,
+    /// Configure existing configurations again from scratch, keeping what they built
+    fresh: bool,
+    /// Whether to color output; `None` decides based on the terminal
+    color: Option<bool>,
+    /// Shell commands to run in each configuration before cmake
+    pre_hooks: Vec<String>,
+    /// Shell commands to run in each configuration after cmake succeeded
+    post_hooks: Vec<String>,
+    /// Give each configuration a git worktree of the project at this revision
+    worktree: Option<String>,
+    /// Run cmake and ctest in a container
+    container: Option<Container>,
+    /// Run cmake and ctest on another machine
+    remote: Option<Remote>,
+    /// Run `conan install` first for projects with a conanfile
+    conan: bool,
+    /// Use vcpkg for projects with a manifest, or when `VCPKG_ROOT` is set
+    vcpkg: bool,
+    /// Those the project uses, when its CMakeLists.txt could be read
+    languages: Option<Vec<String>>,
+    /// Filled in by `check_cmake`
+    cmake_version: Option<String>,
+}
+
+/// Every configuration gets a compile database for clangd and friends.
+const EXPORT_COMPILE_COMMANDS_ARG: &str = "-DCMAKE_EXPORT_COMPILE_COMMANDS=ON";
+
+/// The arguments cmake gets for configuring `conf`.
+fn cmake_args(conf: &Config, settings: &Settings, project_dir: &Path) -> Vec<OsString> {
+    let build_system = conf.build_system.unwrap_or(settings.build_system);
+    let mut args = vec![
+        build_system.as_cmake_arg().to_string(),
+        EXPORT_COMPILE_COMMANDS_ARG.to_string(),
+    ];
+    args.extend(
+        conf.compiler
+            .as_cmake_args()
+            .into_iter()
+            .filter(|arg| compiler_arg_used(arg, settings)),
     );
-    opts.optflag("h", "help", "print this help menu");
-    let matches = match opts.parse(args) {
-        Ok(m) => m,
-        Err(e) => return (1, Some(format!("{}", e))),
+    if let Some(build_type) = conf.build_type {
+        args.push(build_type.as_cmake_arg().to_string());
+    }
+    args.extend(conf.cmake_args.iter().cloned());
+    args.extend(settings.common_args.iter().cloned());
+    let mut os_args = vec![project_dir.as_os_str().to_os_string()];
+    os_args.extend(args.into_iter().map(OsString::from));
+    os_args
+}
+
+/// Whether a `-DCMAKE_<LANG>_COMPILER` argument is for a language the project uses.
+///
+/// Assembly and Objective-C go through the C and C++ compilers unless told otherwise.
+fn compiler_arg_used(arg: &str, settings: &Settings) -> bool {
+    let languages = match settings.languages {
+        Some(ref languages) => languages,
+        None => return true,
     };
-    if matches.opt_present("h") {
-        print_usage(&program, &opts);
-        return (1, None);
+    let users: &[&str] = if arg.starts_with("-DCMAKE_C_COMPILER=") {
+        &["C", "ASM", "ASM-ATT", "OBJC"]
+    } else if arg.starts_with("-DCMAKE_CXX_COMPILER=") {
+        &["CXX", "OBJCXX", "HIP"]
+    } else {
+        return true;
+    };
+    languages
+        .iter()
+        .any(|language| users.contains(&&language[..]))
+}
+
+/// The compilers and analyzers `conf` needs that aren't installed, which it can't be configured
+/// without.
+fn missing_compilers(conf: &Config, settings: &Settings) -> Vec<String> {
+    // Those run the compilers elsewhere
+    if settings.remote.is_some() || settings.container.is_some() {
+        return Vec::new();
     }
-    let arg = match matches.free.get(0) {
-        Some(arg) => arg,
-        None => {
-            print_usage(&program, &opts);
-            return (1, None);
+    let mut missing = Vec::new();
+    let compiler_args = conf.compiler.as_cmake_args().into_iter();
+    for arg in compiler_args.chain(conf.cmake_args.iter().cloned()) {
+        let program = match arg.strip_prefix("-DCMAKE_").and_then(|a| a.split_once('=')) {
+            Some((var, program)) if var.ends_with("_COMPILER") => program.to_string(),
+            Some((var, program)) if ANALYZERS.iter().any(|a| var.ends_with(a.variable)) => {
+                program.to_string()
+            }
+            _ => continue,
+        };
+        if !installed(&program) && compiler_arg_used(&arg, settings) && !missing.contains(&program)
+        {
+            missing.push(program);
+        }
+    }
+    missing
+}
+
+/// Why `conf` can't be configured on this machine, if its compilers are missing.
+fn missing_compilers_reason(conf: &Config, settings: &Settings) -> Option<String> {
+    let missing = missing_compilers(conf, settings);
+    match missing.len() {
+        0 => None,
+        1 => Some(format!("{} isn't installed", missing[0])),
+        n => Some(format!(
+            "{} and {} aren't installed",
+            missing[..n - 1].join(", "),
+            missing[n - 1]
+        )),
+    }
+}
+
+/// Leaves out the configurations whose compilers are missing, with a warning for each.
+///
+/// Those needing something their compiler can't do, like a sanitizer, go quietly.
+fn skip_missing_compilers(configs: &mut Vec<&Config>, settings: &Settings) {
+    configs.retain(|conf| match missing_compilers_reason(conf, settings) {
+        Some(reason) => {
+            warn(&format!("Skipping {}, {}", conf.name, reason));
+            false
         }
+        None => true,
+    });
+    // The compilers that count are elsewhere
+    if settings.remote.is_some() || settings.container.is_some() {
+        return;
+    }
+    let mut probe = probe::Probe::load();
+    configs.retain(|conf| {
+        let (compiler, cxx) = match probed_compiler(conf) {
+            Some(compiler) => compiler,
+            None => return true,
+        };
+        let unsupported = needed_flags(conf)
+            .into_iter()
+            .find(|flag| !probe.supports(&compiler, cxx, flag));
+        match unsupported {
+            Some(flag) => {
+                log::info(&format!(
+                    "Leaving out {}, {} doesn't support {}",
+                    conf.name, compiler, flag
+                ));
+                false
+            }
+            None => true,
+        }
+    });
+    probe.save();
+}
+
+/// Compiler flags that configurations may use but not every compiler has, besides sanitizers.
+const PROBED_FLAGS: [&str; 2] = ["-m32", "-stdlib=libc++"];
+
+/// The flags `conf` can't be built without, which its compiler might not support.
+fn needed_flags(conf: &Config) -> Vec<String> {
+    let mut flags: Vec<String> = sanitizers::from_args(&conf.cmake_args)
+        .iter()
+        .map(|sanitizer| format!("-fsanitize={}", sanitizer))
+        .collect();
+    for arg in &conf.cmake_args {
+        let value = match arg.strip_prefix("-DCMAKE_").and_then(|a| a.split_once('=')) {
+            Some((var, value)) if var.contains("_FLAGS") => value,
+            _ => continue,
+        };
+        for flag in value.split_whitespace() {
+            if PROBED_FLAGS.contains(&flag) && !flags.iter().any(|f| f == flag) {
+                flags.push(flag.to_string());
+            }
+        }
+    }
+    flags
+}
+
+/// The compiler to probe for `conf`, the C++ one unless there is only a C one, and whether it's
+/// for C++.
+///
+/// Toolchain files set up compilers of their own.
+fn probed_compiler(conf: &Config) -> Option<(String, bool)> {
+    let args = conf.compiler.as_cmake_args();
+    let compiler = |language: &str| {
+        let prefix = format!("-DCMAKE_{}_COMPILER=", language);
+        args.iter()
+            .find_map(|arg| arg.strip_prefix(&prefix[..]).map(str::to_string))
     };
-    let proj_dir = std::env::current_dir().unwrap().join(&arg);
-    match std::fs::metadata(&proj_dir) {
-        Ok(_) => {}
-        Err(e) => {
-            return (
-                1,
-                Some(
-                    format!(
-                        "Error while trying to look up directory {:?}: {}",
-                        proj_dir,
-                        e
-                    ),
-                ),
-            );
+    match compiler("CXX") {
+        Some(cxx) => Some((cxx, true)),
+        None => compiler("C").map(|c| (c, false)),
+    }
+}
+
+fn shell_quote(arg: &str) -> String {
+    let is_safe = |c: char| c.is_ascii_alphanumeric() || "-_./=:,+@%".contains(c);
+    if !arg.is_empty() && arg.chars().all(is_safe) {
+        arg.to_string()
+    } else {
+        format!("'{}'", arg.replace('\'', "'\\''"))
+    }
+}
+
+fn shell_command_line<S: AsRef<OsStr>>(program: &str, args: &[S]) -> String {
+    let mut line = shell_quote(program);
+    for arg in args {
+        line.push(' ');
+        line.push_str(&shell_quote(&arg.as_ref().to_string_lossy()));
+    }
+    line
+}
+
+/// `cmd` as it would be typed into a shell, with its environment but not where it runs.
+fn command_line(cmd: &Command) -> String {
+    let mut line = String::new();
+    let mut envs = cmd.get_envs().peekable();
+    if envs.peek().is_some() {
+        line.push_str("env ");
+    }
+    for (key, value) in envs {
+        let key = key.to_string_lossy();
+        match value {
+            Some(value) => {
+                let var = format!("{}={}", key, value.to_string_lossy());
+                line += &format!("{} ", shell_quote(&var));
+            }
+            None => line += &format!("-u {} ", shell_quote(&key)),
         }
     }
-    let props = match parse_cmakelists_txt(&proj_dir) {
-        Ok(props) => props,
-        Err(e) => {
-            return (
-                1,
-                Some(
-                    format!("Failed to open CMakeLists.txt in {:?}: {}", proj_dir, e),
-                ),
-            );
+    let args: Vec<_> = cmd.get_args().collect();
+    line + &shell_command_line(&cmd.get_program().to_string_lossy(), &args)
+}
+
+fn configure_command(
+    conf: &Config,
+    dir: &Path,
+    settings: &Settings,
+    project_dir: &Path,
+) -> Command {
+    let mut cmd = Command::new(&settings.cmake);
+    if settings.fresh && has_fresh(settings) {
+        cmd.arg("--fresh");
+    }
+    cmd.args(cmake_args(conf, settings, project_dir))
+        .envs(conf.env.iter().cloned())
+        .current_dir(dir);
+    let cmd = containerize(cmd, settings, &[project_dir]);
+    remotize(cmd, settings)
+}
+
+/// Whether the cmake of `settings` takes `--fresh`, which is new in 3.24.
+fn has_fresh(settings: &Settings) -> bool {
+    settings
+        .cmake_version
+        .as_ref()
+        .and_then(|version| parse_version(version))
+        .is_some_and(|version| version >= (3, 24))
+}
+
+/// Empties configuration `dir` of what cmake generated, for `--fresh` without `cmake --fresh`.
+///
+/// What got built stays, so the next build only redoes what changed.
+fn forget_cache(dir: &Path) -> Result<(), String> {
+    let cache = dir.join("CMakeCache.txt");
+    std::fs::remove_file(&cache).map_err(|e| format!("Failed to delete {:?}: {}", cache, e))?;
+    let files = dir.join("CMakeFiles");
+    if files.is_dir() {
+        std::fs::remove_dir_all(&files)
+            .map_err(|e| format!("Failed to delete {:?}: {}", files, e))?;
+    }
+    Ok(())
+}
+
+/// Wraps `cmd` to run over ssh on the remote of `settings`, if there is one.
+///
+/// The directory it runs in gets created there first.
+fn remotize(cmd: Command, settings: &Settings) -> Command {
+    let remote = match settings.remote {
+        Some(ref remote) => remote,
+        None => return cmd,
+    };
+    let dir = remote.dir(cmd.get_current_dir().unwrap_or_else(|| Path::new(".")));
+    let dir = shell_quote(&dir.to_string_lossy());
+    let mut line = format!("mkdir -p {0} && cd {0} &&", dir);
+    for (key, value) in cmd.get_envs() {
+        if let Some(value) = value {
+            let value = shell_quote(&value.to_string_lossy());
+            line += &format!(" {}={}", key.to_string_lossy(), value);
         }
+    }
+    // The remote has its own installation, wherever that may be
+    let program = file_name(Path::new(cmd.get_program()));
+    let args: Vec<&OsStr> = cmd.get_args().collect();
+    line.push(' ');
+    line.push_str(&shell_command_line(&program, &args));
+    let mut ssh = Command::new("ssh");
+    ssh.arg(&remote.host).arg(line);
+    ssh
+}
+
+/// Wraps `cmd` to run in the container of `settings`, if there is one.
+///
+/// The directory it runs in gets mounted, along with `mounts` and the source directory the
+/// configuration has in its cache. Paths stay the same inside, so the cache makes sense outside.
+fn containerize(cmd: Command, settings: &Settings, mounts: &[&Path]) -> Command {
+    let container = match settings.container {
+        Some(ref container) => container,
+        None => return cmd,
     };
-    let build_dir = PathBuf::from(format!("build-{}", arg));
-    if build_dir.exists() {
-        return (
-            1,
-            Some(
-                format!(
-                    "The build directory ({:?}) already exists. Delete it first.",
-                    build_dir
-                ),
-            ),
-        );
+    let cwd = std::env::current_dir().unwrap();
+    let dir = cwd.join(cmd.get_current_dir().unwrap_or(&cwd));
+    let mut dirs: Vec<PathBuf> = mounts.iter().map(|path| cwd.join(path)).collect();
+    if let Ok(cache) = CMakeCache::read(&dir.join("CMakeCache.txt")) {
+        if let Some(source) = cache.get("CMAKE_HOME_DIRECTORY") {
+            dirs.push(PathBuf::from(source));
+        }
     }
-    std::fs::create_dir(&build_dir).unwrap();
-    std::env::set_current_dir(&build_dir).unwrap();
-    let mut configs = vec![
-        config("Debug", Gcc, Debug, &[]),
-        config("Release", Gcc, Release, &[]),
-        config("Debug", Clang, Debug, &[]),
-        config("Release", Clang, Release, &[]),
-    ];
-    if props.has_sanitize && !matches.opt_present("no-sanitize") {
-        configs.extend(
-            vec![
-                config("Asan", Clang, Debug, &["-DSANITIZE=address"]),
-                config("Ubsan", Clang, Debug, &["-DSANITIZE=undefined"]),
-                config("Tsan", Clang, Debug, &["-DSANITIZE=thread"]),
-            ],
-        );
+    dirs.push(dir.clone());
+    let mut wrapped = Command::new(&container.engine);
+    wrapped.args(["run", "--rm"]);
+    if container.engine.contains("podman") {
+        wrapped.arg("--userns=keep-id");
+    } else if let (Some(uid), Some(gid)) = (id("-u"), id("-g")) {
+        // Otherwise everything in the build directory belongs to root
+        wrapped.arg("--user").arg(format!("{}:{}", uid, gid));
     }
-    let build_system = if matches.opt_present("no-ninja") {
-        BuildSystem::Make
+    for mount in &dirs {
+        let mut volume = mount.clone().into_os_string();
+        volume.push(":");
+        volume.push(mount);
+        wrapped.arg("-v").arg(volume);
+    }
+    wrapped.arg("-w").arg(&dir);
+    for (key, value) in cmd.get_envs() {
+        if let Some(value) = value {
+            let mut var = key.to_os_string();
+            var.push("=");
+            var.push(value);
+            wrapped.arg("-e").arg(var);
+        }
+    }
+    // Whatever path the host has it at is of no use in there
+    let program = Path::new(cmd.get_program()).file_name().unwrap_or_default();
+    wrapped
+        .arg(&container.image)
+        .arg(program)
+        .args(cmd.get_args());
+    wrapped
+}
+
+/// Our user or group id, for `-u` and `-g` respectively.
+fn id(flag: &str) -> Option<String> {
+    let output = Command::new("id").arg(flag).output().ok()?;
+    let id = String::from_utf8_lossy(&output.stdout).trim().to_string();
+    Some(id).filter(|id| output.status.success() && !id.is_empty())
+}
+
+const CONFIGURE_LOG: &str = "mkqcb-configure.log";
+
+/// Runs cmake for `conf` in the new directory `dir`, along with the hooks and package managers.
+///
+/// Returns the exit status of the last step that got to run, along with any output that still
+/// needs to be shown: the captured output if `capture` is set, and the log of a failed
+/// configuration in quiet mode. A configuration cmake didn't generate anything for has no status.
+/// The cmake command comes along if it got as far as running it, with what the package managers
+/// add to it.
+fn create_config(
+    conf: &Config,
+    dir: &Path,
+    settings: &Settings,
+    project_dir: &Path,
+    capture: bool,
+) -> (Option<ExitStatus>, Vec<u8>, Option<Command>) {
+    // With --fresh, it's there already
+    let created = if settings.fresh {
+        std::fs::create_dir_all(dir)
     } else {
-        BuildSystem::Ninja
+        std::fs::create_dir(dir)
     };
-    for c in configs {
-        use ansi_term::Colour::{Green, Yellow, White};
-        println!(
-            "{0} {1} {2} {0}",
-            Green.bold().paint("==="),
-            White.bold().paint("Creating configuration for"),
-            Yellow.bold().paint(&c.name[..])
-        );
-        if !create_config(&c, build_system, proj_dir.to_str().unwrap()) {
+    if let Err(e) = created {
+        let msg = format!("Failed to create {:?}: {}\n", dir, e);
+        return (None, msg.into_bytes(), None);
+    }
+    if settings.fresh && !has_fresh(settings) && dir.join("CMakeCache.txt").is_file() {
+        if let Err(e) = forget_cache(dir) {
+            return (None, (e + "\n").into_bytes(), None);
+        }
+    }
+    let local_project_dir = project_dir;
+    let project = project_name(local_project_dir);
+    let source;
+    let project_dir = match settings.worktree {
+        Some(ref rev) => match add_worktree(project_dir, rev, dir) {
+            Ok(worktree_source) => {
+                source = worktree_source;
+                &source
+            }
+            Err(e) => return (None, (e + "\n").into_bytes(), None),
+        },
+        None => match settings.remote {
+            Some(ref remote) => {
+                source = remote.project_dir(project_dir);
+                &source
+            }
+            None => project_dir,
+        },
+    };
+    let mut output = Vec::new();
+    for hook in &settings.pre_hooks {
+        let cmd = hook_command(hook, "pre", conf, dir, &project, project_dir);
+        let (status, out) = run_step(cmd, dir, settings, capture);
+        output.extend(out);
+        if !status.is_some_and(|s| s.success()) {
+            return (status, output, None);
+        }
+    }
+    let mut dependency_args = Vec::new();
+    if settings.conan && conan::uses_conan(local_project_dir) {
+        log::debug("Installing dependencies with Conan, the project has a conanfile");
+        let cmd = conan::install_command("conan", conf, project_dir, dir);
+        let cmd = containerize(cmd, settings, &[project_dir]);
+        let (status, out) = run_step(remotize(cmd, settings), dir, settings, capture);
+        output.extend(out);
+        if !status.is_some_and(|s| s.success()) {
+            return (status, output, None);
+        }
+        dependency_args.push(format!(
+            "-DCMAKE_TOOLCHAIN_FILE={}",
+            conan::toolchain(dir).display()
+        ));
+    }
+    if settings.vcpkg {
+        let args = vcpkg::root(local_project_dir).and_then(|root| match root {
+            // The installation and the triplets written for it are on this machine
+            Some(_) if settings.remote.is_some() || settings.container.is_some() => Err(
+                "vcpkg only works where mkqcb runs, not with --remote or --container. \
+                 Pass --no-vcpkg to do without it."
+                    .to_string(),
+            ),
+            Some(root) => {
+                log::debug(&format!("Using vcpkg from {}", root.display()));
+                vcpkg::cmake_args(conf, &root, dir)
+            }
+            None => Ok(Vec::new()),
+        });
+        match args {
+            Ok(args) => dependency_args.extend(args),
+            Err(e) => {
+                output.extend((e + "\n").into_bytes());
+                return (None, output, None);
+            }
+        }
+    }
+    let with_dependencies;
+    let conf = if dependency_args.is_empty() {
+        conf
+    } else {
+        let mut extended = conf.clone();
+        extended.cmake_args.extend(dependency_args);
+        with_dependencies = extended;
+        &with_dependencies
+    };
+    // The reply would be on the remote machine
+    let file_api = settings.remote.is_none();
+    if file_api {
+        if let Err(e) = fileapi::write_query(dir) {
+            output.extend((e + "\n").into_bytes());
+            return (None, output, None);
+        }
+    }
+    // Running it uses it up, and reconfigure.sh wants it too
+    let command = configure_command(conf, dir, settings, project_dir);
+    let cmd = configure_command(conf, dir, settings, project_dir);
+    let (mut status, out) = run_step(cmd, dir, settings, capture);
+    output.extend(out);
+    if file_api && status.is_some_and(|s| s.success()) {
+        if let Err(e) = verify_model(dir, settings) {
+            output.extend((e + "\n").into_bytes());
+            return (None, output, None);
+        }
+    }
+    for hook in &settings.post_hooks {
+        if !status.is_some_and(|s| s.success()) {
             break;
         }
+        let cmd = hook_command(hook, "post", conf, dir, &project, project_dir);
+        let (hook_status, out) = run_step(cmd, dir, settings, capture);
+        output.extend(out);
+        status = hook_status;
+    }
+    (status, output, Some(command))
+}
+
+/// Checks that cmake described what it generated in `dir`, in a file api reply.
+///
+/// No targets at all is fine, projects that only install files have none.
+fn verify_model(dir: &Path, settings: &Settings) -> Result<(), String> {
+    // The file api is new in 3.14
+    let replies = settings
+        .cmake_version
+        .as_ref()
+        .and_then(|version| parse_version(version))
+        .is_some_and(|version| version >= (3, 14));
+    match fileapi::read_reply(dir)? {
+        Some(_) => Ok(()),
+        None if replies => {
+            Err("cmake succeeded, but didn't describe what it generated".to_string())
+        }
+        None => Ok(()),
+    }
+}
+
+/// Where configurations keep their worktree with `--worktrees`.
+const WORKTREE_DIR: &str = "src";
+
+/// Checks out `rev` of the repository of `project_dir` into the configuration directory `dir`.
+///
+/// Returns where the project is in the new worktree, it needn't be at the top of the repository.
+fn add_worktree(project_dir: &Path, rev: &str, dir: &Path) -> Result<PathBuf, String> {
+    let output = Command::new("git")
+        .arg("-C")
+        .arg(project_dir)
+        .args(["rev-parse", "--show-prefix"])
+        .output()
+        .map_err(|e| format!("Failed to run git: {}", e))?;
+    if !output.status.success() {
+        return Err(format!(
+            "--worktrees needs a git checkout, {:?} isn't one",
+            project_dir
+        ));
+    }
+    let prefix = String::from_utf8_lossy(&output.stdout)
+        .trim()
+        .trim_end_matches('/')
+        .to_string();
+    let worktree = std::env::current_dir()
+        .unwrap()
+        .join(dir)
+        .join(WORKTREE_DIR);
+    let output = Command::new("git")
+        .arg("-C")
+        .arg(project_dir)
+        .args(["worktree", "add", "--detach"])
+        .arg(&worktree)
+        .arg(rev)
+        .output()
+        .map_err(|e| format!("Failed to run git: {}", e))?;
+    if !output.status.success() {
+        return Err(format!(
+            "Failed to add a worktree at {:?}:\n{}",
+            worktree,
+            String::from_utf8_lossy(&output.stderr).trim_end()
+        ));
+    }
+    Ok(worktree.join(prefix))
+}
+
+fn has_worktree(dir: &Path) -> bool {
+    dir.join(WORKTREE_DIR).join(".git").is_file()
+}
+
+/// Makes git forget the worktrees of configurations that have been deleted.
+fn prune_worktrees(proj_dir: &Path) {
+    let _ = Command::new("git")
+        .arg("-C")
+        .arg(proj_dir)
+        .args(["worktree", "prune"])
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .status();
+}
+
+/// A shell running `hook` in `dir`, with the details of the configuration in the environment.
+///
+/// `project` is what `{project}` stands for, `project_dir` may be a worktree or on another machine.
+fn hook_command(
+    hook: &str,
+    when: &str,
+    conf: &Config,
+    dir: &Path,
+    project: &str,
+    project_dir: &Path,
+) -> Command {
+    let build_dir = std::env::current_dir().unwrap().join(dir);
+    let build_type = conf.build_type.map_or("", |b| b.name());
+    let mut cmd = Command::new("sh");
+    cmd.arg("-c")
+        .arg(expand_placeholders(hook, conf, project, &build_dir))
+        .envs(conf.env.iter().cloned())
+        .env("MKQCB_HOOK", when)
+        .env("MKQCB_CONFIG_NAME", &conf.name)
+        .env("MKQCB_BUILD_DIR", build_dir)
+        .env("MKQCB_SOURCE_DIR", project_dir)
+        .env("MKQCB_COMPILER", conf.compiler.to_string())
+        .env("MKQCB_BUILD_TYPE", build_type)
+        .current_dir(dir);
+    cmd
+}
+
+/// Runs one step of configuring `dir`: cmake, or a hook.
+///
+/// Output is handled the same way as for `create_config`. In quiet mode, the steps share a log.
+fn run_step(
+    mut cmd: Command,
+    dir: &Path,
+    settings: &Settings,
+    capture: bool,
+) -> (Option<ExitStatus>, Vec<u8>) {
+    use std::fs;
+    let program = cmd.get_program().to_string_lossy().into_owned();
+    let fail = |msg: String| (None, msg.into_bytes());
+    log::command(&cmd);
+    if settings.quiet {
+        let log_path = dir.join(CONFIGURE_LOG);
+        let log = fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&log_path)
+            .and_then(|log| Ok((log.try_clone()?, log)));
+        let (stdout, stderr) = match log {
+            Ok(log) => log,
+            Err(e) => return fail(format!("Failed to create {:?}: {}\n", log_path, e)),
+        };
+        cmd.stdout(Stdio::from(stdout)).stderr(Stdio::from(stderr));
+        match run_interruptible(&mut cmd) {
+            Ok(status) if status.success() => (Some(status), Vec::new()),
+            Ok(status) => (Some(status), fs::read(&log_path).unwrap_or_default()),
+            Err(e) => fail(format!("Failed to run {}: {}\n", program, e)),
+        }
+    } else if capture {
+        match output_interruptible(&mut cmd) {
+            Ok((status, text)) => (Some(status), text),
+            Err(e) => fail(format!("Failed to run {}: {}\n", program, e)),
+        }
+    } else {
+        match run_interruptible(cmd.stdout(child_stdout())) {
+            Ok(status) => (Some(status), Vec::new()),
+            Err(e) => fail(format!("Failed to run {}: {}\n", program, e)),
+        }
+    }
+}
+
+/// Set once the user hits Ctrl-C
+static INTERRUPTED: AtomicBool = AtomicBool::new(false);
+
+fn interrupted() -> bool {
+    INTERRUPTED.load(Ordering::SeqCst)
+}
+
+/// Waits for `child`, killing it if we get interrupted in the meantime.
+fn wait_interruptible(child: &mut Child) -> std::io::Result<ExitStatus> {
+    loop {
+        if let Some(status) = child.try_wait()? {
+            return Ok(status);
+        }
+        if interrupted() {
+            let _ = child.kill();
+            return child.wait();
+        }
+        std::thread::sleep(Duration::from_millis(50));
+    }
+}
+
+fn run_interruptible(cmd: &mut Command) -> std::io::Result<ExitStatus> {
+    wait_interruptible(&mut cmd.spawn()?)
+}
+
+/// Like `run_interruptible`, but collects stdout followed by stderr.
+fn output_interruptible(cmd: &mut Command) -> std::io::Result<(ExitStatus, Vec<u8>)> {
+    use std::io::Read;
+    fn read_all<R: Read + Send + 'static>(pipe: Option<R>) -> std::thread::JoinHandle<Vec<u8>> {
+        std::thread::spawn(move || {
+            let mut buf = Vec::new();
+            if let Some(mut pipe) = pipe {
+                let _ = pipe.read_to_end(&mut buf);
+            }
+            buf
+        })
+    }
+    let mut child = cmd.stdout(Stdio::piped()).stderr(Stdio::piped()).spawn()?;
+    let stdout = read_all(child.stdout.take());
+    let stderr = read_all(child.stderr.take());
+    let status = wait_interruptible(&mut child)?;
+    let mut text = stdout.join().unwrap_or_default();
+    text.extend(stderr.join().unwrap_or_default());
+    Ok((status, text))
+}
+
+/// What happened to a configuration during a run.
+struct ConfigResult<'a> {
+    config: &'a Config,
+    dir: PathBuf,
+    /// cmake's exit status, if it got to run at all
+    status: Option<ExitStatus>,
+    duration: Duration,
+    /// Configured by an earlier run and left alone
+    existing: bool,
+    built: Option<bool>,
+    build_duration: Option<Duration>,
+    tested: Option<bool>,
+    /// What cmake says it generated
+    model: Option<fileapi::Model>,
+    /// How cmake got run, for reconfigure.sh
+    command: Option<Command>,
+}
+
+impl<'a> ConfigResult<'a> {
+    fn configured(&self) -> bool {
+        self.existing || self.status.is_some_and(|s| s.success())
+    }
+}
+
+/// Tracks how far along a run is.
+struct Progress {
+    total: usize,
+    done: AtomicUsize,
+    start: Instant,
+    /// Draw a single updating line instead of a banner per configuration
+    bar: bool,
+}
+
+impl Progress {
+    fn new(total: usize, settings: &Settings) -> Self {
+        Progress {
+            total,
+            done: Default::default(),
+            start: Instant::now(),
+            bar: settings.quiet && progress_is_terminal(),
+        }
+    }
+    /// Banner announcing the `n`th configuration.
+    fn banner(&self, n: usize, name: &str) -> String {
+        use ansi_term::Colour::{Green, White, Yellow};
+        let action = format!("[{}/{}] Creating configuration for", n, self.total);
+        let elapsed = format!("({} elapsed)", format_duration(self.start.elapsed()));
+        format!(
+            "{0} {1} {2} {3} {0}\n",
+            paint(Green.bold(), "==="),
+            paint(White.bold(), action),
+            paint(Yellow.bold(), name),
+            paint(White.dimmed(), elapsed)
+        )
+    }
+    fn draw_bar(&self, done: usize, name: &str) -> String {
+        const WIDTH: usize = 30;
+        let filled = WIDTH * done / self.total.max(1);
+        format!(
+            "\r[{}{}] {}/{} {} ({})\x1b[K",
+            "#".repeat(filled),
+            "-".repeat(WIDTH - filled),
+            done,
+            self.total,
+            name,
+            format_duration(self.start.elapsed())
+        )
+    }
+    /// Announces that a configuration is about to be created.
+    fn starting(&self, n: usize, name: &str) {
+        let text = if self.bar {
+            self.draw_bar(n - 1, name)
+        } else {
+            self.banner(n, name)
+        };
+        let mut out = progress_output();
+        let _ = out.write_all(text.as_bytes());
+        let _ = out.flush();
+    }
+    /// Reports a finished configuration along with its output.
+    ///
+    /// Unless `announced`, the banner is printed along with the output.
+    fn finished(&self, name: &str, output: &[u8], announced: bool) {
+        let n = self.done.fetch_add(1, Ordering::SeqCst) + 1;
+        let mut block = Vec::new();
+        if self.bar {
+            if !output.is_empty() {
+                block.extend(b"\r\x1b[K");
+                block.extend(self.banner(n, name).into_bytes());
+                block.extend(output);
+            }
+            block.extend(self.draw_bar(n, name).into_bytes());
+        } else {
+            if !announced {
+                block.extend(self.banner(n, name).into_bytes());
+            }
+            block.extend(output);
+        }
+        let _ = progress_output().write_all(&block);
+    }
+    /// Moves past the progress bar once all configurations are done.
+    fn end(&self) {
+        if self.bar {
+            let _ = writeln!(progress_output());
+        }
+    }
+}
+
+/// Creates the configurations of `layout`, `settings.jobs` at a time.
+///
+/// Returns the results for the configurations that were attempted.
+/// Once one fails, no new ones are started, unless `settings.keep_going`.
+fn create_configs<'a>(
+    configs: &[&'a Config],
+    layout: &Layout,
+    settings: &Settings,
+    project_dir: &Path,
+) -> Vec<ConfigResult<'a>> {
+    use std::sync::Mutex;
+    let result = |config, dir: PathBuf, status: Option<ExitStatus>, cmd, duration| ConfigResult {
+        config,
+        model: if status.is_some_and(|s| s.success()) {
+            fileapi::read_reply(&dir).ok().flatten()
+        } else {
+            None
+        },
+        dir,
+        status,
+        duration,
+        existing: false,
+        built: None,
+        build_duration: None,
+        tested: None,
+        command: cmd,
+    };
+    let progress = Progress::new(configs.len(), settings);
+    if settings.jobs <= 1 {
+        let mut results = Vec::new();
+        for (i, &c) in configs.iter().enumerate() {
+            if interrupted() {
+                break;
+            }
+            progress.starting(i + 1, &c.name);
+            let dir = layout.dir(c);
+            let start = Instant::now();
+            let (status, output, command) = create_config(c, &dir, settings, project_dir, false);
+            progress.finished(&c.name, &output, true);
+            results.push(result(c, dir, status, command, start.elapsed()));
+            if !status.is_some_and(|s| s.success()) && !settings.keep_going {
+                break;
+            }
+        }
+        progress.end();
+        return results;
+    }
+    let next = AtomicUsize::new(0);
+    let failed = AtomicBool::new(false);
+    let results = Mutex::new(Vec::new());
+    // Output is captured and printed in one go per configuration, so it doesn't get mixed up
+    let worker = || {
+        while !failed.load(Ordering::SeqCst) && !interrupted() {
+            let i = next.fetch_add(1, Ordering::SeqCst);
+            let c = match configs.get(i) {
+                Some(&c) => c,
+                None => break,
+            };
+            let dir = layout.dir(c);
+            let start = Instant::now();
+            let (status, output, command) = create_config(c, &dir, settings, project_dir, true);
+            let duration = start.elapsed();
+            progress.finished(&c.name, &output, false);
+            if !status.is_some_and(|s| s.success()) && !settings.keep_going {
+                failed.store(true, Ordering::SeqCst);
+            }
+            results
+                .lock()
+                .unwrap()
+                .push((i, result(c, dir, status, command, duration)));
+        }
+    };
+    if progress.bar {
+        progress.starting(1, "");
+    }
+    std::thread::scope(|scope| {
+        for _ in 0..settings.jobs.min(configs.len()) {
+            scope.spawn(worker);
+        }
+    });
+    progress.end();
+    let mut results = results.into_inner().unwrap();
+    results.sort_by_key(|&(i, _)| i);
+    results.into_iter().map(|(_, result)| result).collect()
+}
+
+/// Set when stdout is reserved for machine readable output
+static PROGRESS_TO_STDERR: AtomicBool = AtomicBool::new(false);
+
+/// Where human readable progress goes.
+fn progress_output() -> Box<dyn Write> {
+    if PROGRESS_TO_STDERR.load(Ordering::Relaxed) {
+        Box::new(std::io::stderr())
+    } else {
+        Box::new(std::io::stdout())
+    }
+}
+
+fn progress_is_terminal() -> bool {
+    use std::io::IsTerminal;
+    if PROGRESS_TO_STDERR.load(Ordering::Relaxed) {
+        std::io::stderr().is_terminal()
+    } else {
+        std::io::stdout().is_terminal()
+    }
+}
+
+/// Output of the tools we run counts as progress too.
+fn child_stdout() -> Stdio {
+    if PROGRESS_TO_STDERR.load(Ordering::Relaxed) {
+        Stdio::from(std::io::stderr())
+    } else {
+        Stdio::inherit()
+    }
+}
+
+extern crate ansi_term;
+
+use ansi_term::{ANSIString, Style};
+
+static COLOR: AtomicBool = AtomicBool::new(true);
+
+/// Sets up where progress goes and whether it's colored.
+fn init_output(settings: &Settings) {
+    PROGRESS_TO_STDERR.store(settings.json, Ordering::Relaxed);
+    let color = settings.color.unwrap_or_else(|| {
+        let no_color = std::env::var_os("NO_COLOR").is_some_and(|v| !v.is_empty());
+        !no_color && progress_is_terminal()
+    });
+    let color = color && enable_ansi_colors();
+    COLOR.store(color, Ordering::Relaxed);
+}
+
+/// Makes the Windows console take the escape codes colors are made of, which it doesn't by default.
+///
+/// Fails on consoles too old for them, output that doesn't go to a console takes them as is.
+#[cfg(windows)]
+fn enable_ansi_colors() -> bool {
+    type Handle = *mut std::ffi::c_void;
+    const STD_OUTPUT_HANDLE: u32 = -11i32 as u32;
+    const STD_ERROR_HANDLE: u32 = -12i32 as u32;
+    const ENABLE_VIRTUAL_TERMINAL_PROCESSING: u32 = 0x4;
+    extern "system" {
+        fn GetStdHandle(which: u32) -> Handle;
+        fn GetConsoleMode(console: Handle, mode: *mut u32) -> i32;
+        fn SetConsoleMode(console: Handle, mode: u32) -> i32;
+    }
+    [STD_OUTPUT_HANDLE, STD_ERROR_HANDLE]
+        .iter()
+        .all(|&which| unsafe {
+            let console = GetStdHandle(which);
+            let mut mode: u32 = 0;
+            GetConsoleMode(console, &mut mode) == 0
+                || SetConsoleMode(console, mode | ENABLE_VIRTUAL_TERMINAL_PROCESSING) != 0
+        })
+}
+
+#[cfg(not(windows))]
+fn enable_ansi_colors() -> bool {
+    true
+}
+
+/// Like `style.paint(text)`, unless color is turned off.
+fn paint<'a, S: Into<std::borrow::Cow<'a, str>>>(style: Style, text: S) -> ANSIString<'a> {
+    if COLOR.load(Ordering::Relaxed) {
+        style.paint(text)
+    } else {
+        Style::new().paint(text)
+    }
+}
+
+fn cache_dir() -> Option<PathBuf> {
+    use std::env;
+    match env::var_os("XDG_CACHE_HOME") {
+        Some(ref dir) if !dir.is_empty() => Some(Path::new(dir).join("mkqcb")),
+        _ => env::var_os("HOME")
+            .map(|home| Path::new(&home).join(".cache/mkqcb"))
+            // Outside of MSYS2, Windows has no HOME
+            .or_else(|| env::var_os("LOCALAPPDATA").map(|dir| Path::new(&dir).join("mkqcb"))),
+    }
+}
+
+/// Where the user's configuration files go, `~/.config` usually.
+fn config_home() -> Option<PathBuf> {
+    use std::env;
+    match env::var_os("XDG_CONFIG_HOME") {
+        Some(ref dir) if !dir.is_empty() => Some(PathBuf::from(dir)),
+        _ => env::var_os("HOME")
+            .map(|home| Path::new(&home).join(".config"))
+            .or_else(|| env::var_os("APPDATA").map(PathBuf::from)),
+    }
+}
+
+/// Disk usage of freshly created configurations, accumulated over previous runs.
+struct UsageHistory {
+    samples: u64,
+    total_bytes: u64,
+}
+
+/// Assumed usage per configuration until there is some history to go by.
+const DEFAULT_CONFIG_USAGE: u64 = 4 * 1024 * 1024;
+
+impl UsageHistory {
+    fn path() -> Option<PathBuf> {
+        cache_dir().map(|dir| dir.join("usage"))
+    }
+    fn load() -> UsageHistory {
+        let mut history = UsageHistory {
+            samples: 0,
+            total_bytes: 0,
+        };
+        let text = match Self::path().and_then(|p| std::fs::read_to_string(p).ok()) {
+            Some(text) => text,
+            None => return history,
+        };
+        let mut fields = text.split_whitespace().map(|f| f.parse::<u64>());
+        if let (Some(Ok(samples)), Some(Ok(total_bytes))) = (fields.next(), fields.next()) {
+            history.samples = samples;
+            history.total_bytes = total_bytes;
+        }
+        history
+    }
+    fn estimate(&self, n_configs: usize) -> u64 {
+        let per_config = match self.samples {
+            0 => DEFAULT_CONFIG_USAGE,
+            n => self.total_bytes / n,
+        };
+        per_config * n_configs as u64
+    }
+    fn record(&mut self, bytes: u64) {
+        self.samples += 1;
+        self.total_bytes += bytes;
+    }
+    fn save(&self) {
+        // Losing the history is no big deal, so errors are ignored
+        if let Some(path) = Self::path() {
+            if let Some(dir) = path.parent() {
+                let _ = std::fs::create_dir_all(dir);
+            }
+            let _ = std::fs::write(path, format!("{} {}\n", self.samples, self.total_bytes));
+        }
+    }
+}
+
+fn dir_size(path: &Path) -> u64 {
+    let meta = match std::fs::symlink_metadata(path) {
+        Ok(meta) => meta,
+        Err(_) => return 0,
+    };
+    if !meta.is_dir() {
+        return meta.len();
+    }
+    match std::fs::read_dir(path) {
+        Ok(entries) => entries
+            .filter_map(|e| e.ok())
+            .map(|e| dir_size(&e.path()))
+            .sum(),
+        Err(_) => 0,
+    }
+}
+
+fn free_space(path: &Path) -> Option<u64> {
+    let out = Command::new("df").arg("-Pk").arg(path).output().ok()?;
+    let text = String::from_utf8(out.stdout).ok()?;
+    let line = text.lines().nth(1)?;
+    let kib: u64 = line.split_whitespace().nth(3)?.parse().ok()?;
+    Some(kib * 1024)
+}
+
+fn format_size(bytes: u64) -> String {
+    const UNITS: [&str; 4] = ["KiB", "MiB", "GiB", "TiB"];
+    let mut size = bytes as f64;
+    let mut unit = "B";
+    for u in &UNITS {
+        if size < 1024.0 {
+            break;
+        }
+        size /= 1024.0;
+        unit = u;
+    }
+    format!("{:.1} {}", size, unit)
+}
+
+/// Make sure the build tree can actually be created in `dir` before starting.
+fn check_destination(dir: &Path, needed: u64) -> Result<(), String> {
+    let probe = dir.join(format!(".mkqcb-probe-{}", std::process::id()));
+    if let Err(e) = std::fs::write(&probe, b"") {
+        return Err(format!("Can't create the build tree in {:?}: {}", dir, e));
+    }
+    let _ = std::fs::remove_file(&probe);
+    if let Some(free) = free_space(dir) {
+        if free < needed {
+            return Err(format!(
+                "Not enough free space in {:?}: the configurations need about {}, \
+                 but only {} is available.",
+                dir,
+                format_size(needed),
+                format_size(free)
+            ));
+        }
+    }
+    Ok(())
+}
+
+fn build_config(dir: &Path, settings: &Settings) -> bool {
+    let mut cmd = Command::new(&settings.cmake);
+    cmd.args(["--build", "."]).current_dir(dir);
+    if let Some(ref target) = settings.build_target {
+        cmd.arg("--target").arg(target);
+    }
+    if let Some(jobs) = settings.build_jobs {
+        cmd.arg("-j").arg(jobs.to_string());
+    }
+    let mut cmd = remotize(containerize(cmd, settings, &[]), settings);
+    log::command(&cmd);
+    run_interruptible(cmd.stdout(child_stdout()))
+        .map(|status| status.success())
+        .unwrap_or(false)
+}
+
+/// The bits of the exit code of `ctest -D`, for the steps that failed.
+///
+/// Configuring is among the build errors, for when the build runs cmake again.
+const DASHBOARD_BUILD_ERRORS: i32 = 0x02 | 0x04;
+const DASHBOARD_TEST_ERRORS: i32 = 0x08;
+const DASHBOARD_SUBMIT_ERRORS: i32 = 0x40;
+
+/// What became of submitting a configuration to the dashboard.
+struct Submission {
+    built: bool,
+    tested: bool,
+    submitted: bool,
+}
+
+/// Builds and tests the configured `dir` through ctest, submitting the results to the dashboard.
+///
+/// The steps are those of `ctest -D Experimental` but for configuring, which is done already.
+fn submit_config(dir: &Path, settings: &Settings) -> Submission {
+    let mut cmd = Command::new(&settings.ctest);
+    for step in ["Start", "Build", "Test", "Submit"] {
+        cmd.arg("-D").arg(format!("Experimental{}", step));
+    }
+    cmd.current_dir(dir);
+    let mut cmd = remotize(containerize(cmd, settings, &[]), settings);
+    log::command(&cmd);
+    let code = run_interruptible(cmd.stdout(child_stdout()))
+        .ok()
+        .and_then(|status| status.code());
+    let failed = |bits: i32| match code {
+        Some(0) => false,
+        // Failed before getting to any of the steps
+        Some(code) if code & 0x7f == 0 => true,
+        Some(code) => code & bits != 0,
+        None => true,
+    };
+    Submission {
+        built: !failed(DASHBOARD_BUILD_ERRORS),
+        tested: !failed(DASHBOARD_TEST_ERRORS),
+        submitted: !failed(DASHBOARD_SUBMIT_ERRORS),
+    }
+}
+
+fn test_config(dir: &Path, settings: &Settings) -> bool {
+    let mut cmd = Command::new(&settings.ctest);
+    cmd.arg("--output-on-failure").current_dir(dir);
+    let mut cmd = remotize(containerize(cmd, settings, &[]), settings);
+    log::command(&cmd);
+    run_interruptible(cmd.stdout(child_stdout()))
+        .map(|status| status.success())
+        .unwrap_or(false)
+}
+
+/// ctest from the same installation as `cmake`, if there is one.
+fn ctest_for(cmake: &str) -> PathBuf {
+    let ctest = format!("ctest{}", std::env::consts::EXE_SUFFIX);
+    let sibling = Path::new(cmake).parent().map(|dir| dir.join(ctest));
+    match sibling {
+        Some(ref ctest) if ctest.is_file() => ctest.clone(),
+        _ => PathBuf::from("ctest"),
+    }
+}
+
+fn print_usage(program: &str, opts: &Options) {
+    let brief = format!(
+        "Usage: {0} project_dir... [options] [-- cmake args...]\n       \
+         {0} --workspace FILE [project_dir...] [options] [-- cmake args...]\n       \
+         {0} from-tarball <file.tar.gz> [options] [-- cmake args...]\n       \
+         {0} update <project_dir> [options] [-- cmake args...]\n       \
+         {0} watch <project_dir> [options] [-- cmake args...]\n       \
+         {0} clean <project_dir> [--configs PATTERNS] [--yes]\n       \
+         {0} list|status <project_dir>\n       \
+         {0} add <project_dir> --compiler COMPILER [--type TYPE] [--name NAME] [options]\n       \
+         {0} remove <project_dir> <configuration>...\n       \
+         {0} regen <project_dir> [--lock FILE] [options]\n       \
+         {0} link <project_dir> <configuration>\n       \
+         {0} diff [project_dir] <configuration> <configuration>\n       \
+         {0} cache [project_dir] <configuration> [VAR_PATTERN]\n       \
+         {0} install [project_dir] <configuration> [options] [-- cmake args...]\n       \
+         {0} time-trace [project_dir] <configuration> [--output json]\n       \
+         {0} ccdb merge <project_dir> [--configs PATTERNS]\n       \
+         {0} completions bash|zsh|fish",
+        program
+    );
+    print!("{}", opts.usage(&brief));
+}
+
+struct CMakeListsProperties {
+    sanitizers: Option<sanitizers::Scheme>,
+    /// Names as cmake has them, like CXX
+    languages: Vec<String>,
+    /// Declared with `option()` or `cmake_dependent_option()`
+    options: Vec<ProjectOption>,
+}
+
+struct ProjectOption {
+    name: String,
+    description: String,
+    /// As written, which may well be a variable reference
+    default: String,
+}
+
+fn parse_cmakelists_txt(path: &Path) -> std::io::Result<CMakeListsProperties> {
+    let mut commands = Vec::new();
+    read_commands(path, &mut commands)?;
+    let mut options: Vec<ProjectOption> = Vec::new();
+    for c in &commands {
+        if c.name != "option" && c.name != "cmake_dependent_option" {
+            continue;
+        }
+        let name = match c.args.first() {
+            Some(name) if !options.iter().any(|o| o.name == *name) => name.clone(),
+            _ => continue,
+        };
+        options.push(ProjectOption {
+            name,
+            description: c.args.get(1).cloned().unwrap_or_default(),
+            default: c.args.get(2).cloned().unwrap_or_else(|| "OFF".to_string()),
+        });
+    }
+    let option_names: Vec<&str> = options.iter().map(|o| &o.name[..]).collect();
+    let sanitizers = sanitizers::Scheme::detect(&commands, &option_names);
+    match sanitizers
+        .as_ref()
+        .and_then(|scheme| scheme.cmake_arg("address"))
+    {
+        Some(arg) => log::debug(&format!("Sanitizers get turned on like {}", arg)),
+        None => log::debug("Found no way to turn on sanitizers"),
+    }
+    let languages = project_languages(&commands);
+    log::debug(&format!("The project uses {}", languages.join(", ")));
+    Ok(CMakeListsProperties {
+        sanitizers,
+        languages,
+        options,
+    })
+}
+
+/// Reads the commands of `dir/CMakeLists.txt`, followed by those of its subdirectories.
+///
+/// Only subdirectories named literally and inside `dir` are followed.
+fn read_commands(dir: &Path, commands: &mut Vec<cmakelists::Command>) -> std::io::Result<()> {
+    let text = std::fs::read_to_string(dir.join("CMakeLists.txt"))?;
+    log::debug(&format!("Reading {}", dir.join("CMakeLists.txt").display()));
+    let parsed = cmakelists::parse(&text);
+    let subdirs: Vec<PathBuf> = parsed
+        .iter()
+        .filter(|c| c.name == "add_subdirectory")
+        .filter_map(|c| c.args.first())
+        .filter(|arg| !arg.contains("${") && !arg.starts_with('/') && !arg.contains(".."))
+        .map(|arg| dir.join(arg))
+        .collect();
+    commands.extend(parsed);
+    for subdir in subdirs {
+        // cmake reports those better than we could
+        let _ = read_commands(&subdir, commands);
+    }
+    Ok(())
+}
+
+/// The languages enabled by `project()` and `enable_language()`.
+fn project_languages(commands: &[cmakelists::Command]) -> Vec<String> {
+    const KEYWORDS: [&str; 4] = ["VERSION", "DESCRIPTION", "HOMEPAGE_URL", "LANGUAGES"];
+    const DEFAULT: [&str; 2] = ["C", "CXX"];
+    let mut languages: Vec<&str> = Vec::new();
+    let mut has_project = false;
+    for c in commands {
+        match &c.name[..] {
+            "project" => {
+                has_project = true;
+                // The first one is the name
+                let args = c.args.get(1..).unwrap_or(&[]);
+                let is_keyword = |arg: &&String| KEYWORDS.contains(&&arg[..]);
+                let listed: Vec<&String> = match args.iter().position(|arg| arg == "LANGUAGES") {
+                    Some(pos) => args[pos + 1..]
+                        .iter()
+                        .take_while(|arg| !is_keyword(arg))
+                        .collect(),
+                    None if args.iter().any(|arg| is_keyword(&arg)) => Vec::new(),
+                    None => args.iter().collect(),
+                };
+                if listed.is_empty() {
+                    languages.extend(DEFAULT);
+                } else {
+                    languages.extend(listed.iter().map(|arg| &arg[..]));
+                }
+            }
+            "enable_language" => languages.extend(
+                c.args
+                    .iter()
+                    .map(|arg| &arg[..])
+                    .filter(|arg| *arg != "OPTIONAL"),
+            ),
+            _ => {}
+        }
+    }
+    if !has_project {
+        languages.extend(DEFAULT);
+    }
+    let mut unique: Vec<String> = Vec::new();
+    for language in languages {
+        let language = language.to_uppercase();
+        if language != "NONE" && !unique.contains(&language) {
+            unique.push(language);
+        }
+    }
+    unique
+}
+
+/// Prints the options of the project for `--list-options`.
+fn list_options(props: &CMakeListsProperties) {
+    if props.options.is_empty() {
+        println!("The project has no options");
+        return;
+    }
+    let name_width = props.options.iter().map(|o| o.name.len()).max();
+    let default_width = props.options.iter().map(|o| o.default.len()).max();
+    for option in &props.options {
+        println!(
+            "{:name_width$}  {:default_width$}  {}",
+            option.name,
+            option.default,
+            option.description,
+            name_width = name_width.unwrap_or(0),
+            default_width = default_width.unwrap_or(0)
+        );
+    }
+}
+
+fn options() -> Options {
+    let mut opts = Options::new();
+    opts.optopt(
+        "",
+        "sanitizers",
+        "The sanitizers to add configurations for, default address,undefined,thread. \
+         Msan is memory, none adds none",
+        "LIST",
+    );
+    opts.optflag("", "no-sanitize", "Same as --sanitizers none");
+    opts.optflag(
+        "",
+        "no-ninja",
+        "Don't use ninja as a build system. Use plain make instead, MinGW's on Windows.",
+    );
+    opts.optopt(
+        "",
+        "cmake",
+        "The cmake executable to use (default: $CMAKE, or cmake from the PATH)",
+        "PATH",
+    );
+    opts.optopt(
+        "",
+        "only",
+        "Only create configurations matching these comma separated patterns",
+        "PATTERNS",
+    );
+    opts.optflag(
+        "i",
+        "interactive",
+        "Pick the configurations to create off a list of all there are",
+    );
+    opts.optopt(
+        "",
+        "profile",
+        "Only create the configurations of a profile: quick, full, ci, analyze, bench, \
+         dynamic, or one of [profiles] in the config files",
+        "NAME",
+    );
+    opts.optopt(
+        "",
+        "launcher",
+        "Compile through a distributed build launcher",
+        "distcc|icecc",
+    );
+    opts.optopt(
+        "",
+        "launcher-jobs",
+        "Number of compile jobs the launcher can run at once",
+        "N",
+    );
+    opts.optmulti(
+        "",
+        "job-pool",
+        "Declare a Ninja job pool in every configuration. \
+         Compiling and linking go to the pools named compile and link",
+        "NAME=N",
+    );
+    opts.optopt(
+        "",
+        "link-jobs",
+        "Link at most N targets at once in each configuration, same as --job-pool link=N",
+        "N",
+    );
+    opts.optflag(
+        "n",
+        "dry-run",
+        "Print the cmake command lines instead of running them",
+    );
+    opts.optopt(
+        "",
+        "jobs",
+        "Number of configurations to create in parallel (default: 1)",
+        "N",
+    );
+    opts.optflag(
+        "q",
+        "quiet",
+        "Write cmake output to a log file in each configuration, only showing it on failure",
+    );
+    opts.optopt(
+        "",
+        "output",
+        "Print a machine readable report on stdout (progress goes to stderr)",
+        "human|json",
+    );
+    opts.optopt(
+        "",
+        "color",
+        "Whether to color output (NO_COLOR is honored in auto mode)",
+        "auto|always|never",
+    );
+    opts.optflag(
+        "k",
+        "keep-going",
+        "Carry on with the other configurations when one fails",
+    );
+    opts.optflag(
+        "",
+        "resume",
+        "Only create the configurations that are missing from an existing build directory",
+    );
+    opts.optflag(
+        "",
+        "fresh",
+        "Configure existing configurations again from an empty cache, keeping what they built",
+    );
+    opts.optflag(
+        "",
+        "keep-failed",
+        "Don't delete the directories of configurations that failed",
+    );
+    opts.optopt(
+        "",
+        "link-compile-commands",
+        "Symlink compile_commands.json of this configuration into the project directory",
+        "CONFIG",
+    );
+    opts.optflag(
+        "",
+        "from-presets",
+        "Create a configuration per configure preset of the project instead of the usual ones",
+    );
+    opts.optflag(
+        "",
+        "emit-presets",
+        "Export the configurations to CMakeUserPresets.json in the project directory",
+    );
+    opts.optopt(
+        "",
+        "output-dir",
+        "Put the build directory in DIR instead of the current directory",
+        "DIR",
+    );
+    opts.optopt(
+        "",
+        "dir-template",
+        "Where configurations go, default build-{project}/{config}. \
+         Also has {compiler}, {type} or {build_type} and {name}, the configuration without \
+         the compiler",
+        "TEMPLATE",
+    );
+    opts.optopt(
+        "",
+        "install-prefix",
+        "Where configurations install to, relative to their build directory unless absolute. \
+         Default install/{config}, with the placeholders of --dir-template",
+        "TEMPLATE",
+    );
+    opts.optflag(
+        "",
+        "qtcreator",
+        "Register the configurations with Qt Creator through CMakeLists.txt.user",
+    );
+    opts.optmulti(
+        "",
+        "pre-hook",
+        "Shell command to run in each configuration before cmake, after those of .mkqcb.toml. \
+         Placeholders get filled in like for -D",
+        "COMMAND",
+    );
+    opts.optmulti(
+        "",
+        "post-hook",
+        "Shell command to run in each configuration once cmake succeeded",
+        "COMMAND",
+    );
+    opts.optflagopt(
+        "",
+        "worktrees",
+        "Configure each configuration against its own git worktree of REV, HEAD by default",
+        "REV",
+    );
+    opts.optopt(
+        "",
+        "container",
+        "Run cmake in a container of IMAGE, with the project and build directories mounted",
+        "IMAGE",
+    );
+    opts.optopt(
+        "",
+        "container-engine",
+        "The program to run containers with (default: podman if installed, otherwise docker)",
+        "PROGRAM",
+    );
+    opts.optopt(
+        "",
+        "remote",
+        "Copy the source to a machine over rsync and run cmake there with ssh",
+        "USER@HOST:/PATH",
+    );
+    opts.optflag(
+        "",
+        "no-conan",
+        "Don't run conan install for projects with a conanfile",
+    );
+    opts.optflag(
+        "",
+        "no-vcpkg",
+        "Don't use the vcpkg toolchain, even with VCPKG_ROOT set or a vcpkg.json",
+    );
+    opts.optflag("", "build", "Build every configuration after creating them");
+    opts.optopt(
+        "",
+        "build-target",
+        "Build this target instead of the default one",
+        "TARGET",
+    );
+    opts.optopt("j", "build-jobs", "Number of parallel build jobs", "N");
+    opts.optflag(
+        "",
+        "test",
+        "Run ctest in every configuration after creating (and building) them",
+    );
+    opts.optflag(
+        "",
+        "dashboard",
+        "Build and test every configuration with the Experimental steps of ctest, \
+         submitting to the CDash of CTestConfig.cmake by the names of the configurations",
+    );
+    opts.optopt(
+        "",
+        "linker",
+        "Link with auto (mold or lld when installed, the default), mold, lld, gold, bfd, \
+         or default (leave it to the compiler)",
+        "auto|default|mold|lld|gold|bfd",
+    );
+    opts.optmulti(
+        "",
+        "toolchain",
+        "Also create Debug and Release configurations using this toolchain file",
+        "FILE",
+    );
+    opts.optmulti(
+        "D",
+        "",
+        "Set a cache variable in every configuration. \
+         The value can have the placeholders of --dir-template, {project} and {build_dir}",
+        "KEY=VALUE",
+    );
+    opts.optmulti(
+        "",
+        "D-for",
+        "Set a cache variable in the configurations matching a pattern",
+        "PATTERN:KEY=VALUE",
+    );
+    opts.optmulti(
+        "",
+        "policy",
+        "Set a default for a CMake policy in every configuration",
+        "CMP<NNNN>=NEW|OLD",
+    );
+    opts.optmulti(
+        "",
+        "strict-cmake",
+        "Enable CMake developer and deprecation warnings for matching configurations",
+        "PATTERN",
+    );
+    opts.optflag(
+        "",
+        "with-wasm",
+        "Also create an Emscripten-Release configuration",
+    );
+    opts.optflag(
+        "",
+        "with-time-trace",
+        "Also create a Clang-TimeTrace configuration, to report on with time-trace once built",
+    );
+    opts.optflag(
+        "",
+        "bench-without-tests",
+        "Turn BUILD_TESTING off in the Bench configurations",
+    );
+    opts.optflag(
+        "",
+        "xcode",
+        "Also create an AppleClang-Xcode configuration, with the Xcode generator (macOS only)",
+    );
+    opts.optflag(
+        "",
+        "universal",
+        "Also create a Clang-Universal configuration, a Release build for arm64 and x86_64 \
+         (macOS only)",
+    );
+    opts.optopt(
+        "",
+        "android-ndk",
+        "Also create configurations using the Android NDK at this path",
+        "PATH",
+    );
+    opts.optopt(
+        "",
+        "android-abi",
+        "Comma separated ABIs to create Android configurations for (default: arm64-v8a)",
+        "ABIS",
+    );
+    opts.optopt(
+        "",
+        "android-platform",
+        "Minimum Android API level for the Android configurations",
+        "LEVEL",
+    );
+    opts.optflag(
+        "",
+        "list-options",
+        "List the option()s of the project instead of creating anything",
+    );
+    opts.optmulti(
+        "",
+        "enable",
+        "Turn on this option() of the project in every configuration",
+        "NAME",
+    );
+    opts.optmulti(
+        "",
+        "disable",
+        "Turn off this option() of the project in every configuration",
+        "NAME",
+    );
+    opts.optopt(
+        "",
+        "cuda-compiler",
+        "For CUDA projects, what compiles the CUDA code (default nvcc, or $CUDACXX)",
+        "nvcc|clang",
+    );
+    opts.optopt(
+        "",
+        "cuda-arch",
+        "For CUDA projects, the CMAKE_CUDA_ARCHITECTURES to build for",
+        "ARCHS",
+    );
+    opts.optopt(
+        "",
+        "compiler",
+        "With add, the compiler to use",
+        "gcc|clang|TOOLCHAIN.cmake|CC[,CXX]",
+    );
+    opts.optopt(
+        "",
+        "type",
+        "With add, the build type (default Debug)",
+        "Debug|Release",
+    );
+    opts.optopt(
+        "",
+        "name",
+        "With add, the configuration name (default COMPILER-TYPE)",
+        "NAME",
+    );
+    opts.optopt(
+        "",
+        "lock",
+        "With regen, the lockfile to recreate the build directory from",
+        "FILE",
+    );
+    opts.optopt(
+        "",
+        "configs",
+        "With clean or ccdb merge, only use configurations matching these comma separated patterns",
+        "PATTERNS",
+    );
+    opts.optflag(
+        "y",
+        "yes",
+        "Don't ask for confirmation before deleting anything",
+    );
+    opts.optopt(
+        "",
+        "workspace",
+        "Also set up the projects listed in FILE, a TOML file with projects = [\"dir\", ...]",
+        "FILE",
+    );
+    opts.optflagmulti(
+        "v",
+        "verbose",
+        "Show the commands that run, given twice also what got detected and why",
+    );
+    opts.optflag("h", "help", "print this help menu");
+    opts
+}
+
+/// Ninja, unless CMAKE_GENERATOR asks for something else.
+fn default_build_system() -> BuildSystem {
+    let generator = match std::env::var("CMAKE_GENERATOR") {
+        Ok(generator) => generator,
+        Err(_) => return BuildSystem::Ninja,
+    };
+    log::debug(&format!("CMAKE_GENERATOR is {:?}", generator));
+    if generator.is_empty() {
+        return BuildSystem::Ninja;
+    }
+    match BuildSystem::from_generator(&generator) {
+        Some(build_system) => build_system,
+        None => {
+            warn(&format!(
+                "Ignoring unsupported CMAKE_GENERATOR {:?}, using Ninja",
+                generator
+            ));
+            BuildSystem::Ninja
+        }
+    }
+}
+
+/// `extra_args` are passed verbatim to every cmake invocation.
+fn settings(matches: &Matches, extra_args: &[String]) -> Result<Settings, String> {
+    let launcher = match matches.opt_str("launcher") {
+        Some(name) => match Launcher::from_name(&name) {
+            Some(launcher) => Some(launcher),
+            None => return Err(format!("Unknown launcher: {}", name)),
+        },
+        None => None,
+    };
+    let launcher_jobs = match matches.opt_str("launcher-jobs") {
+        Some(jobs) => match jobs.parse() {
+            Ok(jobs) => Some(jobs),
+            Err(e) => return Err(format!("Invalid --launcher-jobs value: {}", e)),
+        },
+        None => None,
+    };
+    let build_system = if matches.opt_present("no-ninja") {
+        BuildSystem::make()
+    } else {
+        default_build_system()
+    };
+    let launcher = launcher.map(|l| (l, launcher_jobs.unwrap_or_else(|| l.default_jobs())));
+    let build_jobs = match matches.opt_str("build-jobs") {
+        Some(jobs) => match jobs.parse() {
+            Ok(jobs) => Some(jobs),
+            Err(e) => return Err(format!("Invalid --build-jobs value: {}", e)),
+        },
+        None => launcher.map(|(_, jobs)| jobs),
+    };
+    let mut common_args = Vec::new();
+    if let Some((launcher, _)) = launcher {
+        common_args.extend(launcher.as_cmake_args().iter().map(|s| s.to_string()));
+    }
+    let pools = job_pools(matches, launcher)?;
+    if let BuildSystem::Ninja = build_system {
+        common_args.extend(job_pool_args(&pools));
+    } else if matches.opt_present("job-pool") || matches.opt_present("link-jobs") {
+        warn("Ignoring --job-pool and --link-jobs, job pools are for Ninja");
+    }
+    common_args.extend(extra_args.iter().cloned());
+    let jobs = match matches.opt_str("jobs") {
+        Some(jobs) => match jobs.parse() {
+            Ok(jobs) => jobs,
+            Err(e) => return Err(format!("Invalid --jobs value: {}", e)),
+        },
+        None => 1,
+    };
+    let json = match matches.opt_str("output").as_ref().map(|s| &s[..]) {
+        None | Some("human") => false,
+        Some("json") => true,
+        Some(other) => return Err(format!("Unknown output format: {}", other)),
+    };
+    let color = match matches.opt_str("color").as_ref().map(|s| &s[..]) {
+        None | Some("auto") => None,
+        Some("always") => Some(true),
+        Some("never") => Some(false),
+        Some(other) => return Err(format!("Unknown color mode: {}", other)),
+    };
+    let cmake = matches
+        .opt_str("cmake")
+        .or_else(|| std::env::var("CMAKE").ok().filter(|s| !s.is_empty()))
+        .unwrap_or_else(|| "cmake".to_string());
+    // The worktree would be checked out again on top of itself
+    if matches.opt_present("fresh") && matches.opt_present("worktrees") {
+        return Err("--fresh can't be combined with --worktrees".to_string());
+    }
+    // Both work with paths of this machine
+    if matches.opt_present("remote")
+        && (matches.opt_present("container") || matches.opt_present("worktrees"))
+    {
+        return Err("--remote can't be combined with --container or --worktrees".to_string());
+    }
+    Ok(Settings {
+        build_system,
+        common_args,
+        launcher_jobs: launcher.map(|(_, jobs)| jobs),
+        ctest: ctest_for(&cmake),
+        cmake,
+        build: matches.opt_present("build"),
+        build_target: matches.opt_str("build-target"),
+        build_jobs,
+        test: matches.opt_present("test"),
+        dashboard: matches.opt_present("dashboard"),
+        jobs,
+        quiet: matches.opt_present("quiet"),
+        json,
+        keep_going: matches.opt_present("keep-going"),
+        keep_failed: matches.opt_present("keep-failed"),
+        fresh: matches.opt_present("fresh"),
+        color,
+        pre_hooks: matches.opt_strs("pre-hook"),
+        post_hooks: matches.opt_strs("post-hook"),
+        conan: !matches.opt_present("no-conan"),
+        vcpkg: !matches.opt_present("no-vcpkg"),
+        languages: None,
+        cmake_version: None,
+        remote: match matches.opt_str("remote") {
+            Some(spec) => Some(Remote::parse(&spec)?),
+            None => None,
+        },
+        container: matches.opt_str("container").map(|image| Container {
+            engine: matches
+                .opt_str("container-engine")
+                .or_else(|| find_in_path("podman").map(|_| "podman".to_string()))
+                .unwrap_or_else(|| "docker".to_string()),
+            image,
+        }),
+        worktree: if matches.opt_present("worktrees") {
+            Some(
+                matches
+                    .opt_str("worktrees")
+                    .unwrap_or_else(|| "HEAD".to_string()),
+            )
+        } else {
+            None
+        },
+    })
+}
+
+/// Fills in what `settings` get from the project.
+///
+/// The hooks of the config files go before those from the command line.
+fn load_project(settings: &mut Settings, proj_dir: &Path) -> Result<(), String> {
+    settings.languages = parse_cmakelists_txt(proj_dir)
+        .ok()
+        .map(|props| props.languages);
+    if settings.dashboard && !proj_dir.join("CTestConfig.cmake").is_file() {
+        return Err(format!(
+            "--dashboard needs a CTestConfig.cmake in {:?}, saying where to submit",
+            proj_dir
+        ));
+    }
+    let file = ConfigFile::load(proj_dir)?;
+    let cli_pre = std::mem::replace(&mut settings.pre_hooks, file.pre_hooks);
+    settings.pre_hooks.extend(cli_pre);
+    let cli_post = std::mem::replace(&mut settings.post_hooks, file.post_hooks);
+    settings.post_hooks.extend(cli_post);
+    Ok(())
+}
+
+/// Work out the configurations to create for a project, out of those matching `only`.
+///
+/// Analyzer, Bench and Valgrind configurations are only there when `only` names them, `*` alone
+/// doesn't. Those of `specs` come last, replacing any of the same name.
+fn plan_configs(
+    matches: &Matches,
+    props: &CMakeListsProperties,
+    only: Option<&str>,
+    specs: &[ConfigSpec],
+) -> Result<Vec<Config>, String> {
+    let mut toolchains = Vec::new();
+    for path in matches.opt_strs("toolchain") {
+        toolchains.push(Compiler::toolchain(&path)?);
+    }
+    let wasm_toolchain = if matches.opt_present("with-wasm") {
+        match find_emscripten_toolchain() {
+            Some(file) => Some(Toolchain {
+                name: "Emscripten".to_string(),
+                file,
+            }),
+            None => {
+                return Err("Could not find the Emscripten toolchain file. \
+                            Activate the emsdk environment or set EMSCRIPTEN."
+                    .to_string())
+            }
+        }
+    } else {
+        None
+    };
+    let android = match matches.opt_str("android-ndk") {
+        Some(ndk) => android_configs(
+            &ndk,
+            matches.opt_str("android-abi").as_ref().map(|s| &s[..]),
+            matches.opt_str("android-platform").as_ref().map(|s| &s[..]),
+        )?,
+        None => Vec::new(),
+    };
+    let mac_only = ["xcode", "universal"];
+    if !cfg!(target_os = "macos") {
+        if let Some(opt) = mac_only.iter().find(|opt| matches.opt_present(opt)) {
+            return Err(format!("--{} is only for macOS", opt));
+        }
+    }
+    let gcc = if cfg!(target_os = "macos") {
+        macos_gcc()
+    } else {
+        Some(Gcc)
+    };
+    let mut configs = Vec::new();
+    if let Some(gcc) = gcc {
+        configs.push(config("Debug", gcc.clone(), Debug, &[]));
+        configs.push(config("Release", gcc, Release, &[]));
+    }
+    configs.push(config("Debug", Clang, Debug, &[]));
+    configs.push(config("Release", Clang, Release, &[]));
+    if matches.opt_present("universal") {
+        configs.push(config(
+            "Universal",
+            Clang,
+            Release,
+            &["-DCMAKE_OSX_ARCHITECTURES=arm64;x86_64"],
+        ));
+    }
+    if matches.opt_present("with-time-trace") {
+        // Headers and templates are the frontend's, which optimizing would only drown out
+        let mut conf = config("TimeTrace", Clang, Debug, &[]);
+        for language in ["C", "CXX"] {
+            conf.cmake_args
+                .push(format!("-DCMAKE_{}_FLAGS=-ftime-trace", language));
+        }
+        configs.push(conf);
+    }
+    if matches.opt_present("xcode") {
+        // Xcode brings its own compiler, and the build type is picked when building
+        let apple_clang = Custom {
+            name: "AppleClang".to_string(),
+            c: None,
+            cxx: None,
+        };
+        let mut xcode = config("Xcode", apple_clang, Debug, &[]);
+        xcode.build_type = None;
+        xcode.build_system = Some(BuildSystem::Xcode);
+        configs.push(xcode);
+    }
+    if let Some(compiler) = Compiler::from_env() {
+        configs.push(config("Debug", compiler.clone(), Debug, &[]));
+        configs.push(config("Release", compiler, Release, &[]));
+    }
+    for toolchain in toolchains {
+        configs.push(config("Debug", toolchain.clone(), Debug, &[]));
+        configs.push(config("Release", toolchain, Release, &[]));
+    }
+    if let Some(toolchain) = wasm_toolchain {
+        let mut wasm = config("Release", toolchain, Release, &[]);
+        wasm.build_system = Some(BuildSystem::Ninja);
+        configs.push(wasm);
+    }
+    configs.extend(android);
+    let wanted = match matches.opt_str("sanitizers") {
+        Some(_) if matches.opt_present("no-sanitize") => {
+            return Err("--no-sanitize and --sanitizers don't go together".to_string())
+        }
+        Some(list) => sanitizers::parse_list(&list)?,
+        None if matches.opt_present("no-sanitize") => Vec::new(),
+        None => sanitizers::DEFAULT_SANITIZERS.to_vec(),
+    };
+    match props.sanitizers {
+        Some(ref scheme) => {
+            for &(name, sanitizer) in &sanitizers::SANITIZERS {
+                if !wanted.contains(&sanitizer) {
+                    continue;
+                }
+                match scheme.cmake_arg(sanitizer) {
+                    Some(arg) => {
+                        let mut conf = config(name, Clang, Debug, &[]);
+                        conf.cmake_args.push(arg);
+                        configs.push(conf);
+                    }
+                    None if matches.opt_present("sanitizers") => warn(&format!(
+                        "The project has no way to turn on the {} sanitizer",
+                        sanitizer
+                    )),
+                    None => {}
+                }
+            }
+        }
+        None if matches.opt_present("sanitizers") && !wanted.is_empty() => {
+            warn("Ignoring --sanitizers, the project has no way to turn sanitizers on")
+        }
+        None => {}
+    }
+    for name in matches
+        .opt_strs("enable")
+        .iter()
+        .chain(&matches.opt_strs("disable"))
+    {
+        if !props.options.iter().any(|o| o.name == *name) {
+            warn(&format!("{} isn't one of the project's options", name));
+        }
+    }
+    if let Some(only) = only {
+        let mut opt_in = bench_configs(&configs, matches.opt_present("bench-without-tests"));
+        opt_in.push(valgrind_config(props));
+        opt_in.extend(analyzer_configs());
+        configs.extend(opt_in.into_iter().filter(|c| names_opt_in(only, &c.name)));
+    }
+    for conf in resolve_specs(specs, &configs)? {
+        configs.retain(|c| c.name != conf.name);
+        configs.push(conf);
+    }
+    only_configs(only, &mut configs)?;
+    if props.languages.iter().any(|language| language == "CUDA") {
+        cuda_configs(matches, &mut configs)?;
+    } else if matches.opt_present("cuda-arch") {
+        warn("Ignoring --cuda-arch, the project doesn't use CUDA");
+    }
+    if props.languages.iter().any(|language| language == "FORTRAN") {
+        fortran_configs(&mut configs);
+    }
+    customize_configs(matches, &mut configs)?;
+    Ok(configs)
+}
+
+/// The configurations of the config files, each with what it extends filled in.
+///
+/// They can extend one another or one of `configs`. Arguments add up and variables get
+/// overridden, the compiler, build type and generator get replaced.
+fn resolve_specs(specs: &[ConfigSpec], configs: &[Config]) -> Result<Vec<Config>, String> {
+    fn resolve(
+        name: &str,
+        specs: &[ConfigSpec],
+        configs: &[Config],
+        chain: &mut Vec<String>,
+    ) -> Result<Config, String> {
+        if chain.iter().any(|n| n == name) {
+            chain.push(name.to_string());
+            return Err(format!(
+                "Configurations extend themselves: {}",
+                chain.join(" -> ")
+            ));
+        }
+        let spec = match specs.iter().find(|s| s.name == name) {
+            Some(spec) => spec,
+            None => {
+                return configs
+                    .iter()
+                    .find(|c| c.name == name)
+                    .cloned()
+                    .ok_or_else(|| {
+                        format!(
+                            "{} extends {}, which isn't a configuration",
+                            chain.last().map_or("", |n| &n[..]),
+                            name
+                        )
+                    })
+            }
+        };
+        chain.push(name.to_string());
+        let mut conf = match spec.extends {
+            Some(ref base) => resolve(base, specs, configs, chain)?,
+            None => match spec.compiler {
+                Some(_) => config("", Gcc, Debug, &[]),
+                None => {
+                    return Err(format!(
+                        "Configuration {} needs a compiler, or something to extend",
+                        name
+                    ))
+                }
+            },
+        };
+        chain.pop();
+        conf.name = name.to_string();
+        if let Some(ref compiler) = spec.compiler {
+            conf.compiler = Compiler::from_name(compiler)?;
+        }
+        if let Some(ref build_type) = spec.build_type {
+            conf.build_type = Some(BuildType::from_name(build_type)?);
+        }
+        if let Some(ref generator) = spec.generator {
+            conf.build_system = Some(
+                BuildSystem::from_generator(generator)
+                    .ok_or_else(|| format!("Unsupported generator for {}: {}", name, generator))?,
+            );
+        }
+        conf.cmake_args.extend(spec.args.iter().cloned());
+        for (key, value) in &spec.env {
+            conf.env.retain(|(k, _)| k != key);
+            conf.env.push((key.clone(), value.clone()));
+        }
+        log::debug(&format!(
+            "{} from the config file: {}",
+            name,
+            conf.cmake_args.join(" ")
+        ));
+        Ok(conf)
+    }
+    specs
+        .iter()
+        .filter(|spec| !spec.is_abstract)
+        .map(|spec| resolve(&spec.name, specs, configs, &mut Vec::new()))
+        .collect()
+}
+
+/// What the GCC configurations use on macOS, where `gcc` is usually AppleClang in disguise.
+///
+/// If it is, that's the newest GCC from Homebrew, which puts the major version in the names, or
+/// nothing, the Clang configurations have AppleClang covered.
+fn macos_gcc() -> Option<Compiler> {
+    let output = Command::new("gcc").arg("--version").output();
+    let is_apple_clang =
+        output.is_ok_and(|output| String::from_utf8_lossy(&output.stdout).contains("Apple clang"));
+    if !is_apple_clang {
+        return Some(Gcc);
+    }
+    let mut dirs: Vec<PathBuf> = std::env::var_os("PATH")
+        .map(|path| std::env::split_paths(&path).collect())
+        .unwrap_or_default();
+    // Where Homebrew goes on Apple silicon and on Intel
+    for dir in ["/opt/homebrew/bin", "/usr/local/bin"] {
+        dirs.push(PathBuf::from(dir));
+    }
+    let mut newest: Option<(u32, PathBuf)> = None;
+    for dir in &dirs {
+        let entries = match std::fs::read_dir(dir) {
+            Ok(entries) => entries,
+            Err(_) => continue,
+        };
+        for entry in entries.filter_map(|entry| entry.ok()) {
+            let name = entry.file_name().to_string_lossy().into_owned();
+            let version = match name.strip_prefix("gcc-").and_then(|v| v.parse().ok()) {
+                Some(version) => version,
+                None => continue,
+            };
+            let is_newer = newest.as_ref().is_none_or(|&(newest, _)| version > newest);
+            if is_newer && dir.join(format!("g++-{}", version)).is_file() {
+                newest = Some((version, dir.clone()));
+            }
+        }
+    }
+    match newest {
+        Some((version, dir)) => {
+            log::debug(&format!(
+                "gcc is AppleClang, using gcc-{} from {}",
+                version,
+                dir.display()
+            ));
+            let program = |name: &str| dir.join(format!("{}-{}", name, version));
+            Some(Custom {
+                name: format!("gcc-{}", version),
+                c: Some(program("gcc").display().to_string()),
+                cxx: Some(program("g++").display().to_string()),
+            })
+        }
+        None => {
+            log::debug("gcc is AppleClang and there's no GCC from Homebrew, leaving GCC out");
+            None
+        }
+    }
+}
+
+/// Gives every configuration the Fortran compiler that goes with its C and C++ compilers.
+fn fortran_configs(configs: &mut [Config]) {
+    for conf in configs {
+        let candidates = match conf.compiler {
+            Gcc => vec!["gfortran".to_string()],
+            // LLVM before 20 calls it flang-new
+            Clang => vec!["flang".to_string(), "flang-new".to_string()],
+            Custom { c: Some(ref c), .. } => {
+                let (dir, name) = match c.rfind(std::path::is_separator) {
+                    Some(pos) => c.split_at(pos + 1),
+                    None => ("", &c[..]),
+                };
+                let names = if name.contains("clang") {
+                    vec![
+                        name.replacen("clang", "flang", 1),
+                        name.replacen("clang", "flang-new", 1),
+                    ]
+                } else if name.contains("gcc") {
+                    vec![name.replacen("gcc", "gfortran", 1)]
+                } else if name.contains("icx") {
+                    vec![name.replacen("icx", "ifx", 1)]
+                } else {
+                    continue;
+                };
+                names
+                    .iter()
+                    .map(|name| format!("{}{}", dir, name))
+                    .collect()
+            }
+            // Left to cmake, or the toolchain file
+            Custom { .. } | Toolchain { .. } => continue,
+        };
+        let fortran = candidates
+            .iter()
+            .find(|program| installed(program))
+            .unwrap_or(&candidates[0]);
+        log::debug(&format!(
+            "Using {} for Fortran with {}, out of {}",
+            fortran,
+            conf.name,
+            candidates.join(", ")
+        ));
+        conf.cmake_args
+            .push(format!("-DCMAKE_Fortran_COMPILER={}", fortran));
+    }
+}
+
+/// Picks the CUDA compiler of every configuration, skipping those that can't have one.
+///
+/// nvcc doesn't take clang as the host compiler, and clang compiles CUDA only for itself.
+fn cuda_configs(matches: &Matches, configs: &mut Vec<Config>) -> Result<(), String> {
+    let with_clang = match matches.opt_str("cuda-compiler").as_ref().map(|s| &s[..]) {
+        None | Some("nvcc") => false,
+        Some("clang") => true,
+        Some(other) => return Err(format!("Unknown CUDA compiler: {}", other)),
+    };
+    let nvcc = std::env::var("CUDACXX")
+        .ok()
+        .filter(|s| !s.is_empty())
+        .map(PathBuf::from)
+        .or_else(|| find_in_path("nvcc"))
+        .or_else(|| Some(PathBuf::from("/usr/local/cuda/bin/nvcc")).filter(|p| p.is_file()));
+    match nvcc {
+        Some(ref nvcc) => log::debug(&format!("Found nvcc at {}", nvcc.display())),
+        None => log::debug("Found no nvcc in CUDACXX, the PATH or /usr/local/cuda"),
+    }
+    let arch_arg = matches
+        .opt_str("cuda-arch")
+        .map(|archs| format!("-DCMAKE_CUDA_ARCHITECTURES={}", archs));
+    configs.retain_mut(|conf| {
+        let host = match conf.compiler {
+            Gcc => Some("g++".to_string()),
+            Clang => Some("clang++".to_string()),
+            Custom { ref cxx, .. } => cxx.clone(),
+            // Whatever the toolchain file says
+            Toolchain { .. } => return true,
+        };
+        let host_is_clang = match conf.compiler {
+            Clang => true,
+            Custom { ref name, .. } => name.contains("clang"),
+            _ => false,
+        };
+        let mut args = Vec::new();
+        if with_clang {
+            if !host_is_clang {
+                warn(&format!(
+                    "Skipping {}, clang only compiles CUDA with clang as the C++ compiler",
+                    conf.name
+                ));
+                return false;
+            }
+            args.push(format!(
+                "-DCMAKE_CUDA_COMPILER={}",
+                host.unwrap_or_else(|| "clang++".to_string())
+            ));
+        } else {
+            // Clang works as the host compiler too, as long as nvcc supports its version
+            if let Some(ref nvcc) = nvcc {
+                args.push(format!("-DCMAKE_CUDA_COMPILER={}", nvcc.display()));
+            }
+            if let Some(host) = host {
+                args.push(format!("-DCMAKE_CUDA_HOST_COMPILER={}", host));
+            }
+        }
+        conf.cmake_args.extend(args);
+        conf.cmake_args.extend(arch_arg.clone());
+        true
+    });
+    if configs.is_empty() {
+        return Err(format!(
+            "None of the configurations can build CUDA with {}",
+            if with_clang { "clang" } else { "nvcc" }
+        ));
+    }
+    Ok(())
+}
+
+/// Applies `--only` or `--profile`, whose patterns are `only`.
+fn only_configs(only: Option<&str>, configs: &mut Vec<Config>) -> Result<(), String> {
+    if let Some(only) = only {
+        configs.retain(|c| matches_patterns(only, &c.name));
+        if configs.is_empty() {
+            return Err(format!("No configurations match {}", only));
+        }
+    }
+    Ok(())
+}
+
+/// The linkers `--linker auto` goes for, fastest first.
+const FAST_LINKERS: [&str; 2] = ["mold", "lld"];
+
+/// The linker for `-fuse-ld` that `--linker` asks for, none to leave it to the compilers.
+fn linker(matches: &Matches) -> Result<Option<String>, String> {
+    let is_installed = |linker: &str| installed(&format!("ld.{}", linker));
+    match matches.opt_str("linker").as_deref() {
+        None | Some("auto") => {
+            // What this machine has says nothing about where cmake runs
+            if matches.opt_present("remote") || matches.opt_present("container") {
+                return Ok(None);
+            }
+            let found = FAST_LINKERS.iter().find(|linker| is_installed(linker));
+            if let Some(linker) = found {
+                log::debug(&format!("Linking with {}, it's installed", linker));
+            }
+            Ok(found.map(|linker| linker.to_string()))
+        }
+        Some("default") => Ok(None),
+        // Whether the compilers can use it gets probed
+        Some(linker) if ["mold", "lld", "gold", "bfd"].contains(&linker) => {
+            Ok(Some(linker.to_string()))
+        }
+        Some(linker) => Err(format!("Unknown linker: {}", linker)),
+    }
+}
+
+/// Makes the configurations that build with the compilers of the machine link with `linker`.
+///
+/// Toolchains bring their own, and so do those setting linker flags already. With `probe`, those
+/// whose compiler can't use it are left alone too, with a warning if `linker` was asked for.
+fn set_linker(
+    configs: &mut [Config],
+    linker: &str,
+    mut probe: Option<&mut probe::Probe>,
+    asked_for: bool,
+) {
+    let flag = format!("-fuse-ld={}", linker);
+    for conf in configs {
+        let own_linker = match conf.compiler {
+            Toolchain { .. } => true,
+            Custom { ref c, ref cxx, .. } => c.is_none() && cxx.is_none(),
+            _ => false,
+        };
+        let has_flags = conf
+            .cmake_args
+            .iter()
+            .any(|arg| arg.contains("_LINKER_FLAGS"));
+        if own_linker || has_flags {
+            continue;
+        }
+        if let (Some(probe), Some((compiler, cxx))) = (probe.as_mut(), probed_compiler(conf)) {
+            if !probe.supports(&compiler, cxx, &flag) {
+                let msg = format!(
+                    "{} can't link with {}, {} keeps its linker",
+                    compiler, linker, conf.name
+                );
+                if asked_for {
+                    warn(&msg);
+                } else {
+                    log::info(&msg);
+                }
+                continue;
+            }
+        }
+        for kind in ["EXE", "SHARED", "MODULE"] {
+            conf.cmake_args
+                .push(format!("-DCMAKE_{}_LINKER_FLAGS={}", kind, flag));
+        }
+    }
+}
+
+/// Adds the arguments that apply to some or all configurations.
+fn customize_configs(matches: &Matches, configs: &mut [Config]) -> Result<(), String> {
+    let mut policy_args = Vec::new();
+    for policy in matches.opt_strs("policy") {
+        policy_args.push(policy_arg(&policy)?);
+    }
+    let mut define_args = Vec::new();
+    for define in matches.opt_strs("D") {
+        define_args.push(define_arg(&define)?);
+    }
+    let mut targeted_define_args = Vec::new();
+    for arg in matches.opt_strs("D-for") {
+        targeted_define_args.push(targeted_define_arg(&arg)?);
+    }
+    let mut option_args = Vec::new();
+    for (flag, value) in [("enable", "ON"), ("disable", "OFF")] {
+        for name in matches.opt_strs(flag) {
+            if name.is_empty() || name.contains('=') {
+                return Err(format!("Invalid option name for --{}: {:?}", flag, name));
+            }
+            option_args.push(format!("-D{}:BOOL={}", name, value));
+        }
+    }
+    if let Some(linker) = linker(matches)? {
+        // The compilers that count are elsewhere
+        let elsewhere = matches.opt_present("remote") || matches.opt_present("container");
+        let mut probe = probe::Probe::load();
+        let asked_for = matches.opt_str("linker").is_some_and(|l| l != "auto");
+        set_linker(
+            configs,
+            &linker,
+            Some(&mut probe).filter(|_| !elsewhere),
+            asked_for,
+        );
+        probe.save();
+    }
+    let strict_patterns = matches.opt_strs("strict-cmake");
+    for c in configs {
+        c.cmake_args.extend(policy_args.iter().cloned());
+        c.cmake_args.extend(option_args.iter().cloned());
+        if strict_patterns.iter().any(|p| matches_pattern(p, &c.name)) {
+            c.cmake_args
+                .extend(STRICT_CMAKE_ARGS.iter().map(|s| s.to_string()));
+        }
+        c.cmake_args.extend(define_args.iter().cloned());
+        for (pattern, arg) in &targeted_define_args {
+            if matches_pattern(pattern, &c.name) {
+                c.cmake_args.push(arg.clone());
+            }
+        }
+    }
+    Ok(())
+}
+
+fn banner(action: &str, name: &str) -> String {
+    use ansi_term::Colour::{Green, Yellow, White};
+    format!(
+        "{0} {1} {2} {0}\n",
+        paint(Green.bold(), "==="),
+        paint(White.bold(), action),
+        paint(Yellow.bold(), name)
+    )
+}
+
+fn print_banner(action: &str, name: &str) {
+    let _ = progress_output().write_all(banner(action, name).as_bytes());
+}
+
+/// How much longer `now` is than `before`, like `+8.0s`.
+fn format_change(now: Duration, before: Duration) -> String {
+    // Less than shows isn't slower or faster
+    if now + Duration::from_millis(50) > before {
+        format!("+{}", format_duration(now.saturating_sub(before)))
+    } else {
+        format!("-{}", format_duration(before - now))
+    }
+}
+
+fn format_duration(d: Duration) -> String {
+    let secs = d.as_secs_f64();
+    if secs < 60.0 {
+        format!("{:.1}s", secs)
+    } else {
+        format!("{}m{:02}s", d.as_secs() / 60, d.as_secs() % 60)
+    }
+}
+
+/// Prints a table of what happened to each configuration.
+///
+/// The times are compared to those of the last run in `stats`, if there was one.
+fn print_summary(
+    configs: &[Config],
+    results: &[ConfigResult],
+    layout: &Layout,
+    settings: &Settings,
+    stats: &Stats,
+) {
+    use ansi_term::Colour::{Green, Red, Yellow};
+    let step = |result: Option<bool>, ok: &str, failed: &str| match result {
+        Some(true) => paint(Green.normal(), format!("{:<8}", ok)),
+        Some(false) => paint(Red.bold(), format!("{:<8}", failed)),
+        None => paint(Style::new(), format!("{:<8}", "-")),
+    };
+    let width = configs
+        .iter()
+        .map(|c| c.name.len())
+        .max()
+        .unwrap_or(0)
+        .max(13);
+    let mut out = progress_output();
+    let history = configs.iter().any(|c| stats.last(&c.name).is_some());
+    // The dashboard builds and tests too
+    let built = settings.build || settings.dashboard;
+    let tested = settings.test || settings.dashboard;
+    let mut header = format!(
+        "{:<width$}  {:<10}  {:>7}  ",
+        "Configuration", "Status", "Time"
+    );
+    if history {
+        header.push_str(" Change  ");
+    }
+    if built {
+        header.push_str("Build     Build time  ");
+        if history {
+            header.push_str(" Change  ");
+        }
+    }
+    if tested {
+        header.push_str("Tests     ");
+    }
+    let models = results.iter().any(|r| r.model.is_some());
+    if models {
+        header.push_str("Targets  Compiler          ");
+    }
+    header.push_str("Directory");
+    let _ = writeln!(out, "\n{}", paint(Style::new().bold(), header));
+    for c in configs {
+        let _ = write!(out, "{:<width$}  ", c.name);
+        match results.iter().find(|r| std::ptr::eq(r.config, c)) {
+            Some(r) => {
+                let status = if r.existing {
+                    paint(Green.normal(), format!("{:<10}", "existing"))
+                } else if r.configured() {
+                    paint(Green.bold(), format!("{:<10}", "configured"))
+                } else if interrupted() {
+                    paint(Red.bold(), format!("{:<10}", "stopped"))
+                } else {
+                    paint(Red.bold(), format!("{:<10}", "failed"))
+                };
+                let time = if r.existing {
+                    "-".to_string()
+                } else {
+                    format_duration(r.duration)
+                };
+                let _ = write!(out, "{}  {:>7}  ", status, time);
+                let last = stats.last(&r.config.name);
+                if history {
+                    let change = match last {
+                        Some(last) if !r.existing => format_change(r.duration, last.configure),
+                        _ => "-".to_string(),
+                    };
+                    let _ = write!(out, "{:>7}  ", change);
+                }
+                if built {
+                    let _ = write!(out, "{}  ", step(r.built, "ok", "failed"));
+                    let (build_time, change) = match r.build_duration {
+                        Some(duration) if r.built == Some(true) => (
+                            format_duration(duration),
+                            match last.and_then(|last| last.build) {
+                                Some(last) => format_change(duration, last),
+                                None => "-".to_string(),
+                            },
+                        ),
+                        _ => ("-".to_string(), "-".to_string()),
+                    };
+                    let _ = write!(out, "{:>10}  ", build_time);
+                    if history {
+                        let _ = write!(out, "{:>7}  ", change);
+                    }
+                }
+                if tested {
+                    let _ = write!(out, "{}  ", step(r.tested, "passed", "failed"));
+                }
+                if models {
+                    let (targets, compiler) = match r.model {
+                        Some(ref model) => (
+                            model.targets.to_string(),
+                            model
+                                .main_toolchain()
+                                .map_or_else(|| "-".to_string(), |t| t.describe()),
+                        ),
+                        None => ("-".to_string(), "-".to_string()),
+                    };
+                    let _ = write!(out, "{:>7}  {:<16}  ", targets, compiler);
+                }
+                let _ = writeln!(out, "{}", r.dir.display());
+            }
+            None => {
+                let status = paint(Yellow.normal(), format!("{:<10}", "skipped"));
+                let _ = write!(out, "{}  {:>7}  ", status, "-");
+                if history {
+                    let _ = write!(out, "{:>7}  ", "-");
+                }
+                if built {
+                    let _ = write!(out, "{}  {:>10}  ", step(None, "", ""), "-");
+                    if history {
+                        let _ = write!(out, "{:>7}  ", "-");
+                    }
+                }
+                if tested {
+                    let _ = write!(out, "{}  ", step(None, "", ""));
+                }
+                if models {
+                    let _ = write!(out, "{:>7}  {:<16}  ", "-", "-");
+                }
+                let _ = writeln!(out, "{}", layout.dir(c).display());
+            }
+        }
+    }
+}
+
+fn json_report(results: &[ConfigResult], settings: &Settings, project_dir: &Path) -> String {
+    let configs: Vec<_> = results
+        .iter()
+        .map(|r| {
+            let c = r.config;
+            json!({
+                "name": c.name,
+                "build_dir": r.dir,
+                "generator": c.build_system.unwrap_or(settings.build_system).generator(),
+                "compiler": c.compiler.to_string(),
+                "build_type": c.build_type.map(|b| b.name()),
+                "cmake_args": cmake_args(c, settings, project_dir)
+                    .iter()
+                    .map(|arg| arg.to_string_lossy())
+                    .collect::<Vec<_>>(),
+                "exit_status": r.status.and_then(|s| s.code()),
+                "success": r.configured(),
+                "existing": r.existing,
+                "duration": r.duration.as_secs_f64(),
+                "built": r.built,
+                "tested": r.tested,
+                "targets": r.model.as_ref().map(|m| m.targets),
+                "toolchains": r.model.as_ref().map(|m| {
+                    m.toolchains
+                        .iter()
+                        .map(|t| {
+                            json!({
+                                "language": t.language,
+                                "compiler_id": t.compiler_id,
+                                "compiler_version": t.compiler_version,
+                                "compiler_path": t.compiler_path,
+                            })
+                        })
+                        .collect::<Vec<_>>()
+                }),
+            })
+        })
+        .collect();
+    let report = json!({
+        "project_dir": project_dir.to_string_lossy(),
+        "cmake_version": settings.cmake_version,
+        "configurations": configs,
+    });
+    serde_json::to_string_pretty(&report).unwrap()
+}
+
+/// Deletes what failed configurations left behind, so a rerun can start over.
+///
+/// If nothing got configured, the whole build directory goes.
+fn remove_failed(results: &[ConfigResult], layout: &Layout) {
+    for r in results.iter().filter(|r| !r.configured()) {
+        log::info(&format!("Removing {}", r.dir.display()));
+        match std::fs::remove_dir_all(&r.dir) {
+            Ok(()) => {}
+            Err(ref e) if e.kind() == std::io::ErrorKind::NotFound => {}
+            Err(e) => warn(&format!("Failed to remove {:?}: {}", r.dir, e)),
+        }
+    }
+    // Only goes if it's empty, the layout might put configurations in a directory of the user's
+    if !results.iter().any(|r| r.configured()) {
+        let _ = std::fs::remove_dir(&layout.root);
+    }
+}
+
+/// The version of the cmake of `settings`, wherever that runs.
+fn cmake_version(settings: &Settings) -> Option<String> {
+    let mut cmd = Command::new(&settings.cmake);
+    cmd.arg("--version");
+    let output = remotize(containerize(cmd, settings, &[]), settings)
+        .output()
+        .ok()?;
+    let text = String::from_utf8_lossy(&output.stdout);
+    let version = text.lines().next()?.strip_prefix("cmake version ")?;
+    Some(version.trim().to_string())
+}
+
+/// The major and minor version of a version like 3.28.1.
+fn parse_version(version: &str) -> Option<(u32, u32)> {
+    let mut parts = version.split(['.', '-']);
+    let major = parts.next()?.parse().ok()?;
+    let minor = parts.next()?.parse().ok()?;
+    Some((major, minor))
+}
+
+/// What's asked of cmake that older versions can't do, with the version that can.
+fn required_cmake(matches: &Matches, settings: &Settings) -> Vec<(&'static str, (u32, u32))> {
+    let mut required = Vec::new();
+    if settings.build && settings.build_jobs.is_some() {
+        required.push(("Building with a number of jobs", (3, 12)));
+    }
+    if matches.opt_present("cuda-arch") {
+        required.push(("--cuda-arch", (3, 18)));
+    }
+    // The presets we write are of version 3
+    if matches.opt_present("emit-presets") {
+        required.push(("--emit-presets", (3, 21)));
+    }
+    required
+}
+
+/// Finds out which cmake `settings` has, and fails if it's too old for the rest of them.
+///
+/// That beats every configuration failing in a way that doesn't say why.
+fn check_cmake(matches: &Matches, settings: &mut Settings) -> Result<(), String> {
+    let version = match cmake_version(settings) {
+        Some(version) => version,
+        None => {
+            return Err(format!(
+                "Failed to run `{} --version`, is cmake installed?",
+                settings.cmake
+            ))
+        }
+    };
+    print_banner("Using cmake", &version);
+    if let Some(current) = parse_version(&version) {
+        for (feature, (major, minor)) in required_cmake(matches, settings) {
+            if current < (major, minor) {
+                return Err(format!(
+                    "{} needs cmake {}.{} or newer, this is {}",
+                    feature, major, minor, version
+                ));
+            }
+        }
+    }
+    settings.cmake_version = Some(version);
+    Ok(())
+}
+
+const DEFAULT_DIR_TEMPLATE: &str = "build-{project}/{config}";
+
+/// Next to the configurations, so installing doesn't need root and every flavor stays apart.
+const DEFAULT_INSTALL_PREFIX: &str = "install/{config}";
+
+/// The placeholders of `--dir-template` that differ between configurations.
+const CONFIG_PLACEHOLDERS: [&str; 5] =
+    ["{compiler}", "{type}", "{build_type}", "{name}", "{config}"];
+
+/// What `{project}` stands for, the name of the project directory.
+fn project_name(project_dir: &Path) -> String {
+    project_dir.file_name().map_or_else(
+        || "project".to_string(),
+        |name| name.to_string_lossy().into_owned(),
+    )
+}
+
+/// The project directory `arg` names, made absolute with `..` and symlinks resolved.
+fn resolve_project_dir(arg: &str) -> PathBuf {
+    let path = std::env::current_dir().unwrap().join(arg);
+    canonicalize(&path).unwrap_or(path)
+}
+
+/// Where the configurations of a project go, according to `--dir-template`.
+struct Layout {
+    /// Holds the configurations and the lockfile
+    root: PathBuf,
+    /// The directory name of each configuration, placeholders and all
+    config_dir: String,
+    /// Where each configuration installs to, relative to the root unless it's absolute
+    install_prefix: String,
+    /// What `{project}` stands for
+    project: String,
+}
+
+impl Layout {
+    /// `{project}` is the name of the directory `project` names, however it names it.
+    fn new(matches: &Matches, project: &str) -> Result<Layout, String> {
+        let template = matches
+            .opt_str("dir-template")
+            .unwrap_or_else(|| DEFAULT_DIR_TEMPLATE.to_string());
+        let mut unknown = template.replace("{project}", "");
+        for placeholder in &CONFIG_PLACEHOLDERS {
+            unknown = unknown.replace(placeholder, "");
+        }
+        if unknown.contains('{') || unknown.contains('}') {
+            return Err(format!(
+                "Unknown placeholder in --dir-template {:?}",
+                template
+            ));
+        }
+        let project = project_name(&resolve_project_dir(project));
+        let template = template.replace("{project}", &project);
+        let path = Path::new(template.trim_end_matches(std::path::is_separator));
+        let config_dir = match path.file_name() {
+            Some(name) => name.to_string_lossy().into_owned(),
+            None => return Err(format!("Invalid --dir-template {:?}", template)),
+        };
+        let parent = path.parent().unwrap_or_else(|| Path::new(""));
+        // Absolute templates ignore --output-dir, as joining replaces the base
+        let root = match matches.opt_str("output-dir") {
+            Some(dir) => Path::new(&dir).join(parent),
+            None if parent == Path::new("") => PathBuf::from("."),
+            None => parent.to_path_buf(),
+        };
+        let root_str = root.to_string_lossy();
+        if CONFIG_PLACEHOLDERS.iter().any(|p| root_str.contains(p)) {
+            return Err(
+                "Only the last component of --dir-template can depend on the configuration"
+                    .to_string(),
+            );
+        }
+        if !CONFIG_PLACEHOLDERS.iter().any(|p| config_dir.contains(p)) {
+            return Err(
+                "--dir-template has to give every configuration its own directory".to_string(),
+            );
+        }
+        let install_prefix = matches
+            .opt_str("install-prefix")
+            .unwrap_or_else(|| DEFAULT_INSTALL_PREFIX.to_string())
+            .replace("{project}", &project);
+        Ok(Layout {
+            root,
+            config_dir,
+            install_prefix,
+            project,
+        })
+    }
+    /// Fills in the placeholders of `template` for `conf`.
+    ///
+    /// `{name}` is the configuration name without the compiler in front.
+    fn expand(template: &str, conf: &Config) -> String {
+        let compiler = conf.compiler.to_string();
+        let name = conf
+            .name
+            .strip_prefix(&format!("{}-", compiler)[..])
+            .unwrap_or(&conf.name);
+        let build_type = conf.build_type.map_or("Default", |b| b.name());
+        template
+            .replace("{compiler}", &compiler)
+            .replace("{type}", build_type)
+            .replace("{build_type}", build_type)
+            .replace("{name}", name)
+            .replace("{config}", &conf.name)
+    }
+    fn dir_name(&self, conf: &Config) -> String {
+        Layout::expand(&self.config_dir, conf)
+    }
+    fn dir(&self, conf: &Config) -> PathBuf {
+        self.root.join(self.dir_name(conf))
+    }
+    fn install_prefix(&self, conf: &Config) -> PathBuf {
+        self.root.join(Layout::expand(&self.install_prefix, conf))
+    }
+    /// Whether a directory with this name could be a configuration of ours.
+    fn is_config_dir(&self, name: &str) -> bool {
+        let mut pattern = self.config_dir.clone();
+        for placeholder in &CONFIG_PLACEHOLDERS {
+            pattern = pattern.replace(placeholder, "*");
+        }
+        matches_pattern(&pattern, name)
+    }
+}
+
+/// Fills in the placeholders of user arguments and hooks, `{project}` and `{build_dir}` on top of
+/// those of `--dir-template`.
+fn expand_placeholders(template: &str, conf: &Config, project: &str, build_dir: &Path) -> String {
+    let template = template
+        .replace("{project}", project)
+        .replace("{build_dir}", &build_dir.display().to_string());
+    Layout::expand(&template, conf)
+}
+
+/// Where `path` in the current directory is for cmake, which is elsewhere with `--remote`.
+fn cmake_side(path: &Path, settings: &Settings) -> PathBuf {
+    match settings.remote {
+        Some(ref remote) => remote.dir(path),
+        None => std::env::current_dir().unwrap().join(path),
+    }
+}
+
+/// Fills in the placeholders of the cmake arguments of every configuration.
+///
+/// Those after `--` get moved to the configurations if they have any, as they differ then.
+fn expand_args(configs: &mut [Config], layout: &Layout, settings: &mut Settings) {
+    let common = if settings.common_args.iter().any(|arg| arg.contains('{')) {
+        std::mem::take(&mut settings.common_args)
+    } else {
+        Vec::new()
+    };
+    for c in configs {
+        let build_dir = cmake_side(&layout.dir(c), settings);
+        let args = c
+            .cmake_args
+            .iter()
+            .chain(&common)
+            .map(|arg| expand_placeholders(arg, c, &layout.project, &build_dir))
+            .collect();
+        c.cmake_args = args;
+    }
+}
+
+/// Gives every configuration its install prefix from the layout, unless it has one already.
+fn set_install_prefixes(configs: &mut [Config], layout: &Layout, settings: &Settings) {
+    let sets_prefix = |arg: &String| {
+        arg.starts_with("-DCMAKE_INSTALL_PREFIX=") || arg.starts_with("-DCMAKE_INSTALL_PREFIX:")
+    };
+    let user_prefix = settings.common_args.iter().any(sets_prefix);
+    for c in configs {
+        if user_prefix || c.cmake_args.iter().any(sets_prefix) {
+            continue;
+        }
+        let prefix = cmake_side(&layout.install_prefix(c), settings);
+        c.cmake_args
+            .push(format!("-DCMAKE_INSTALL_PREFIX={}", prefix.display()));
+    }
+}
+
+/// Has CDash show the builds of `configs` by their names, rather than by system and compiler.
+fn set_build_names(configs: &mut [Config]) {
+    for c in configs {
+        let named = |arg: &String| arg.starts_with("-DBUILDNAME");
+        if !c.cmake_args.iter().any(named) {
+            c.cmake_args.push(format!("-DBUILDNAME={}", c.name));
+        }
+    }
+}
+
+/// The configuration directories in the layout, sorted by name.
+fn existing_configs(layout: &Layout) -> Result<Vec<PathBuf>, String> {
+    let entries = std::fs::read_dir(&layout.root)
+        .map_err(|e| format!("Failed to read build directory {:?}: {}", layout.root, e))?;
+    let mut dirs: Vec<PathBuf> = entries
+        .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+        .filter(|path| layout.is_config_dir(&file_name(path)))
+        .filter(|path| path.join("CMakeCache.txt").is_file())
+        .collect();
+    dirs.sort();
+    Ok(dirs)
+}
+
+fn file_name(path: &Path) -> String {
+    path.file_name().unwrap().to_string_lossy().into_owned()
+}
+
+/// Reruns cmake in every existing configuration of a project.
+///
+/// The cache keeps the generator and compilers, so only new arguments need passing.
+fn update(matches: &Matches, arg: &str, extra_args: &[String]) -> Result<(), String> {
+    let settings = settings(matches, extra_args)?;
+    init_output(&settings);
+    let args = update_args(matches, &settings)?;
+    let layout = Layout::new(matches, arg)?;
+    let dirs = configs_to_update(matches, &layout)?;
+    update_configs(&dirs, &args, &settings)
+}
+
+/// The existing configurations, narrowed down by `--only`.
+fn configs_to_update(matches: &Matches, layout: &Layout) -> Result<Vec<PathBuf>, String> {
+    let mut dirs = existing_configs(layout)?;
+    if let Some(only) = matches.opt_str("only") {
+        dirs.retain(|dir| matches_patterns(&only, &file_name(dir)));
+    }
+    if dirs.is_empty() {
+        return Err(format!("No configurations to update in {:?}", layout.root));
+    }
+    Ok(dirs)
+}
+
+fn update_args(matches: &Matches, settings: &Settings) -> Result<Vec<String>, String> {
+    // The caches are on the remote, and so is the source cmake would reread
+    if settings.remote.is_some() {
+        return Err("--remote doesn't work with update or watch".to_string());
+    }
+    let mut args = Vec::new();
+    for define in matches.opt_strs("D") {
+        args.push(define_arg(&define)?);
+    }
+    args.extend(settings.common_args.iter().cloned());
+    Ok(args)
+}
+
+fn update_configs(dirs: &[PathBuf], args: &[String], settings: &Settings) -> Result<(), String> {
+    let mut failed = Vec::new();
+    for dir in dirs {
+        if interrupted() {
+            return Err("Interrupted".to_string());
+        }
+        let name = file_name(dir);
+        print_banner("Updating configuration", &name);
+        let mut cmd = Command::new(&settings.cmake);
+        cmd.arg(".").args(args).current_dir(dir);
+        let cmd = containerize(cmd, settings, &[]);
+        // The log of configuring it the first time is of no interest anymore
+        let _ = std::fs::remove_file(dir.join(CONFIGURE_LOG));
+        let (status, output) = run_step(cmd, dir, settings, false);
+        let _ = progress_output().write_all(&output);
+        if !status.is_some_and(|s| s.success()) {
+            failed.push(name);
+            if !settings.keep_going {
+                break;
+            }
+        }
+    }
+    if failed.is_empty() {
+        Ok(())
+    } else {
+        Err(format!("Failed to update {}", failed.join(", ")))
+    }
+}
+
+/// How often watch mode looks for changes.
+const WATCH_INTERVAL: Duration = Duration::from_millis(500);
+
+type ScriptTimes = std::collections::HashMap<PathBuf, std::time::SystemTime>;
+
+/// Collects the modification times of the CMake scripts in `dir` and below.
+fn cmake_script_times(dir: &Path, times: &mut ScriptTimes) {
+    let entries = match std::fs::read_dir(dir) {
+        Ok(entries) => entries,
+        Err(_) => return,
+    };
+    for entry in entries.filter_map(|entry| entry.ok()) {
+        let path = entry.path();
+        let name = file_name(&path);
+        // Not following symlinks keeps loops out
+        let is_dir = entry.file_type().is_ok_and(|t| t.is_dir());
+        if is_dir {
+            // Build directories are full of generated .cmake files
+            if !name.starts_with('.') && !path.join("CMakeCache.txt").exists() {
+                cmake_script_times(&path, times);
+            }
+        } else if name == "CMakeLists.txt" || name.ends_with(".cmake") {
+            if let Some(time) = modified(&path) {
+                times.insert(path, time);
+            }
+        }
+    }
+}
+
+/// Reruns cmake in the configurations of a project whenever one of its CMake scripts changes.
+fn watch(matches: &Matches, arg: &str, extra_args: &[String]) -> Result<(), String> {
+    let settings = settings(matches, extra_args)?;
+    init_output(&settings);
+    let proj_dir = resolve_project_dir(arg);
+    let layout = Layout::new(matches, arg)?;
+    let args = update_args(matches, &settings)?;
+    // Fails early if there is nothing to update
+    configs_to_update(matches, &layout)?;
+    let scan = || {
+        let mut times = ScriptTimes::new();
+        cmake_script_times(&proj_dir, &mut times);
+        times
+    };
+    let mut times = scan();
+    if times.is_empty() {
+        return Err(format!("There are no CMake scripts in {:?}", proj_dir));
+    }
+    let _ = writeln!(
+        progress_output(),
+        "Watching {} CMake scripts in {}, Ctrl-C stops",
+        times.len(),
+        proj_dir.display()
+    );
+    while !interrupted() {
+        std::thread::sleep(WATCH_INTERVAL);
+        let current = scan();
+        let changed = current
+            .iter()
+            .find(|&(path, time)| times.get(path) != Some(time))
+            .map(|(path, _)| path)
+            .or_else(|| times.keys().find(|path| !current.contains_key(*path)));
+        let changed = match changed {
+            Some(path) => path.clone(),
+            None => continue,
+        };
+        let _ = writeln!(progress_output(), "{} changed", changed.display());
+        let result = configs_to_update(matches, &layout)
+            .and_then(|dirs| update_configs(&dirs, &args, &settings));
+        match result {
+            Err(_) if interrupted() => break,
+            Err(e) => warn(&e),
+            Ok(()) => {}
+        }
+        // Anything cmake wrote into the source tree doesn't count as a change
+        times = scan();
+    }
+    Ok(())
+}
+
+/// Recreates the configurations recorded in a lockfile.
+fn regen(matches: &Matches, arg: &str) -> Result<(), String> {
+    let proj_dir = resolve_project_dir(arg);
+    if let Err(e) = std::fs::metadata(&proj_dir) {
+        return Err(format!(
+            "Error while trying to look up directory {:?}: {}",
+            proj_dir, e
+        ));
+    }
+    let mut settings = settings(matches, &[])?;
+    load_project(&mut settings, &proj_dir)?;
+    init_output(&settings);
+    let layout = Layout::new(matches, arg)?;
+    let lock_path = match matches.opt_str("lock") {
+        Some(path) => PathBuf::from(path),
+        None => Lock::path(&layout.root),
+    };
+    let lock = Lock::read(&lock_path)?;
+    check_cmake(matches, &mut settings)?;
+    if let (Some(version), Some(current)) = (&lock.cmake_version, &settings.cmake_version) {
+        if version != current {
+            warn(&format!(
+                "{:?} was created with cmake {}, this is {}",
+                lock_path, version, current
+            ));
+        }
+    }
+    // The recorded arguments are complete already
+    settings.common_args.clear();
+    if matches.opt_present("dry-run") {
+        for c in &lock.configs {
+            println!("{} ({})", c.name, layout.dir(c).display());
+            let args = cmake_args(c, &settings, &proj_dir);
+            println!("  {}", shell_command_line(&settings.cmake, &args));
+        }
+        return Ok(());
+    }
+    create_tree(matches, &lock.configs, &settings, &proj_dir, &layout)
+}
+
+/// Creates one more configuration in an existing build directory.
+fn add(matches: &Matches, arg: &str, extra_args: &[String]) -> Result<(), String> {
+    let proj_dir = resolve_project_dir(arg);
+    let mut settings = settings(matches, extra_args)?;
+    load_project(&mut settings, &proj_dir)?;
+    init_output(&settings);
+    let layout = Layout::new(matches, arg)?;
+    if !layout.root.is_dir() {
+        return Err(format!(
+            "There is no build directory {:?}, run `mkqcb {}` first",
+            layout.root, arg
+        ));
+    }
+    let compiler = match matches.opt_str("compiler") {
+        Some(name) => Compiler::from_name(&name)?,
+        None => return Err("add needs --compiler".to_string()),
+    };
+    let build_type = match matches.opt_str("type") {
+        Some(name) => BuildType::from_name(&name)?,
+        None => Debug,
+    };
+    let name = matches
+        .opt_str("name")
+        .unwrap_or_else(|| format!("{}-{}", compiler, build_type.name()));
+    let mut configs = vec![Config {
+        name,
+        compiler,
+        build_type: Some(build_type),
+        cmake_args: Vec::new(),
+        build_system: None,
+        env: Vec::new(),
+    }];
+    customize_configs(matches, &mut configs)?;
+    expand_args(&mut configs, &layout, &mut settings);
+    set_install_prefixes(&mut configs, &layout, &settings);
+    if settings.dashboard {
+        set_build_names(&mut configs);
+    }
+    check_cmake(matches, &mut settings)?;
+    let conf = &configs[0];
+    let dir = layout.dir(conf);
+    let project_dir: &Path = &proj_dir;
+    if matches.opt_present("dry-run") {
+        println!("{} ({})", conf.name, dir.display());
+        let args = cmake_args(conf, &settings, project_dir);
+        println!("  {}", shell_command_line(&settings.cmake, &args));
+        return Ok(());
+    }
+    if dir.exists() {
+        return Err(format!("{:?} already exists", dir));
+    }
+    if let Some(reason) = missing_compilers_reason(conf, &settings) {
+        return Err(format!("Can't create {}, {}", conf.name, reason));
+    }
+    let mut usage = UsageHistory::load();
+    check_destination(&layout.root, usage.estimate(1))?;
+    print_banner("Creating configuration for", &conf.name);
+    let (status, output, _) = create_config(conf, &dir, &settings, project_dir, false);
+    let _ = progress_output().write_all(&output);
+    if !status.is_some_and(|s| s.success()) {
+        if !settings.keep_failed {
+            let _ = std::fs::remove_dir_all(&dir);
+        }
+        return Err(format!("Failed to create {}", conf.name));
+    }
+    usage.record(dir_size(&dir));
+    usage.save();
+    let lock_path = Lock::path(&layout.root);
+    if lock_path.exists() {
+        let mut lock = Lock::read(&lock_path)?;
+        lock.configs.extend(Lock::new(&configs, &settings).configs);
+        lock.write(&lock_path)?;
+        if matches.opt_present("emit-presets") || presets::exported(&proj_dir) {
+            presets::export(&proj_dir, &layout, &lock.configs)?;
+        }
+        if matches.opt_present("qtcreator") || qtcreator::exported(&proj_dir) {
+            qtcreator::write(&proj_dir, &layout, &lock.configs)?;
+        }
+    }
+    if settings.dashboard {
+        print_banner("Submitting to the dashboard", &conf.name);
+        let submission = submit_config(&dir, &settings);
+        if !submission.built {
+            return Err(format!("Failed to build {}", conf.name));
+        }
+        if !submission.submitted {
+            return Err(format!("Failed to submit {}", conf.name));
+        }
+        if !submission.tested {
+            return Err("Some tests failed".to_string());
+        }
+        return Ok(());
+    }
+    if settings.build {
+        print_banner("Building configuration", &conf.name);
+        if !build_config(&dir, &settings) {
+            return Err(format!("Failed to build {}", conf.name));
+        }
+    }
+    if settings.test {
+        print_banner("Testing configuration", &conf.name);
+        if !test_config(&dir, &settings) {
+            return Err("Some tests failed".to_string());
+        }
+    }
+    Ok(())
+}
+
+/// Deletes configurations from a build directory by the names of their directories.
+fn remove(matches: &Matches, arg: &str, names: &[String]) -> Result<(), String> {
+    let layout = Layout::new(matches, arg)?;
+    if names.is_empty() {
+        return Err("remove needs the names of the configurations to delete".to_string());
+    }
+    // Check them all first, so a typo doesn't leave the job half done
+    for name in names {
+        let dir = layout.root.join(name);
+        if name.contains(std::path::is_separator) || !dir.join("CMakeCache.txt").is_file() {
+            return Err(format!(
+                "There is no configuration {:?} in {:?}",
+                name, layout.root
+            ));
+        }
+    }
+    let mut worktrees = false;
+    for name in names {
+        let dir = layout.root.join(name);
+        worktrees |= has_worktree(&dir);
+        log::info(&format!("Deleting {}", dir.display()));
+        std::fs::remove_dir_all(&dir).map_err(|e| format!("Failed to delete {:?}: {}", dir, e))?;
+    }
+    if worktrees {
+        prune_worktrees(&resolve_project_dir(arg));
+    }
+    let lock_path = Lock::path(&layout.root);
+    if lock_path.exists() {
+        let mut lock = Lock::read(&lock_path)?;
+        lock.configs
+            .retain(|c| !names.contains(&layout.dir_name(c)));
+        lock.write(&lock_path)?;
+        let proj_dir = resolve_project_dir(arg);
+        if presets::exported(&proj_dir) {
+            presets::export(&proj_dir, &layout, &lock.configs)?;
+        }
+        if qtcreator::exported(&proj_dir) {
+            qtcreator::write(&proj_dir, &layout, &lock.configs)?;
+        }
+    }
+    Ok(())
+}
+
+/// Cache variables that are bound to differ between configurations.
+const EXPECTED_CACHE_DIFFERENCES: [&str; 14] = [
+    "CMAKE_ADDR2LINE",
+    "CMAKE_AR",
+    "CMAKE_C_COMPILER",
+    "CMAKE_C_COMPILER_AR",
+    "CMAKE_C_COMPILER_RANLIB",
+    "CMAKE_CXX_COMPILER",
+    "CMAKE_CXX_COMPILER_AR",
+    "CMAKE_CXX_COMPILER_RANLIB",
+    "CMAKE_LINKER",
+    "CMAKE_NM",
+    "CMAKE_OBJCOPY",
+    "CMAKE_OBJDUMP",
+    "CMAKE_RANLIB",
+    "CMAKE_STRIP",
+];
+
+/// Where configuration `name` of `project` is, or `name` itself if that's a configuration.
+fn config_dir(matches: &Matches, project: &str, name: &str) -> Result<PathBuf, String> {
+    let dir = Layout::new(matches, project)?.root.join(name);
+    if dir.join("CMakeCache.txt").is_file() {
+        Ok(dir)
+    } else if Path::new(name).join("CMakeCache.txt").is_file() {
+        Ok(PathBuf::from(name))
+    } else {
+        Err(format!("There is no configuration {:?} in {:?}", name, dir))
+    }
+}
+
+/// Builds configuration `name` of `project` and installs it into its prefix.
+fn install(
+    matches: &Matches,
+    project: &str,
+    name: &str,
+    extra_args: &[String],
+) -> Result<(), String> {
+    let mut settings = settings(matches, extra_args)?;
+    init_output(&settings);
+    let dir = config_dir(matches, project, name)?;
+    let path = dir.join("CMakeCache.txt");
+    let cache = CMakeCache::read(&path).map_err(|e| format!("Failed to read {:?}: {}", path, e))?;
+    print_banner("Installing configuration", name);
+    // The install target depends on everything else, so that gets built first
+    settings.build_target = Some("install".to_string());
+    if !build_config(&dir, &settings) {
+        return Err(format!("Failed to install {}", name));
+    }
+    match cache.get("CMAKE_INSTALL_PREFIX") {
+        Some(prefix) => println!("Installed {} to {}", name, prefix),
+        None => println!("Installed {}", name),
+    }
+    Ok(())
+}
+
+/// Shows the cache variables two configurations don't agree on.
+///
+/// Their own directories don't count as a difference, neither do the tools each uses.
+fn diff_configs(matches: &Matches, project: &str, a: &str, b: &str) -> Result<(), String> {
+    use ansi_term::Colour::{Green, Red};
+    init_output(&settings(matches, &[])?);
+    let read = |name| -> Result<(PathBuf, CMakeCache), String> {
+        let dir = config_dir(matches, project, name)?;
+        let path = dir.join("CMakeCache.txt");
+        let cache =
+            CMakeCache::read(&path).map_err(|e| format!("Failed to read {:?}: {}", path, e))?;
+        Ok((dir, cache))
+    };
+    let (a_dir, a_cache) = read(a)?;
+    let (b_dir, b_cache) = read(b)?;
+    let a_vars = a_cache.variables();
+    let b_vars = b_cache.variables();
+    let mut keys: Vec<&str> = a_vars.iter().chain(&b_vars).map(|&(key, _)| key).collect();
+    keys.sort();
+    keys.dedup();
+    let canonical = |dir: &Path| std::fs::canonicalize(dir).unwrap_or_else(|_| dir.to_path_buf());
+    let (a_root, b_root) = (canonical(&a_dir), canonical(&b_dir));
+    // With the directories out of the way, what's left are real differences
+    let normalize = |value: &str, root: &Path| value.replace(&*root.to_string_lossy(), "<dir>");
+    let width = a.len().max(b.len());
+    let mut differences = 0;
+    let mut expected = 0;
+    for key in keys {
+        let a_value = a_cache.get(key).map(|value| normalize(value, &a_root));
+        let b_value = b_cache.get(key).map(|value| normalize(value, &b_root));
+        if a_value == b_value {
+            continue;
+        }
+        if EXPECTED_CACHE_DIFFERENCES.contains(&key) {
+            expected += 1;
+            continue;
+        }
+        differences += 1;
+        let line = |name: &str, value: Option<String>| {
+            let value = value.unwrap_or_else(|| "(unset)".to_string());
+            format!("{:<width$}  {}", name, value)
+        };
+        println!("{}", paint(Style::new().bold(), key));
+        println!("  {}", paint(Red.normal(), line(a, a_value)));
+        println!("  {}", paint(Green.normal(), line(b, b_value)));
+    }
+    match differences {
+        0 => println!("{} and {} have the same cache", a, b),
+        1 => println!("1 variable differs"),
+        n => println!("{} variables differ", n),
+    }
+    if expected > 0 {
+        println!("(not counting {} for the compilers and tools)", expected);
+    }
+    Ok(())
+}
+
+/// Prints the cache entries of a configuration whose names match `pattern`, or all of them.
+///
+/// Without a `*`, a pattern matches anywhere in the name, regardless of case. Internal entries only
+/// show with a pattern.
+fn show_cache(
+    matches: &Matches,
+    project: &str,
+    name: &str,
+    pattern: Option<&str>,
+) -> Result<(), String> {
+    use ansi_term::Colour::Yellow;
+    init_output(&settings(matches, &[])?);
+    let path = config_dir(matches, project, name)?.join("CMakeCache.txt");
+    let cache = CMakeCache::read(&path).map_err(|e| format!("Failed to read {:?}: {}", path, e))?;
+    let given = pattern;
+    let pattern = pattern.map(|pattern| {
+        let pattern = pattern.to_uppercase();
+        if pattern.contains('*') {
+            pattern
+        } else {
+            format!("*{}*", pattern)
+        }
+    });
+    let entries: Vec<_> = cache
+        .entries()
+        .into_iter()
+        .filter(|&(key, entry)| match pattern {
+            Some(ref pattern) => matches_pattern(pattern, &key.to_uppercase()),
+            None => !entry.is_internal(),
+        })
+        .collect();
+    if entries.is_empty() {
+        return Err(format!(
+            "No cache entries of {} match {}",
+            name,
+            given.unwrap_or("")
+        ));
+    }
+    let width = entries
+        .iter()
+        .map(|&(key, _)| key.len())
+        .max()
+        .unwrap_or(0)
+        .max(8);
+    let header = format!("{:<width$}  {:<8}  {:<8}  Value", "Variable", "Type", "");
+    println!("{}", paint(Style::new().bold(), header));
+    // With just the one, there's room for what it's for
+    let single = entries.len() == 1;
+    for (key, entry) in entries {
+        let advanced = if cache.is_advanced(key) {
+            "advanced"
+        } else {
+            ""
+        };
+        println!(
+            "{:<width$}  {:<8}  {}  {}",
+            key,
+            entry.ty,
+            paint(Yellow.normal(), format!("{:<8}", advanced)),
+            entry.value
+        );
+        if single && !entry.help.is_empty() {
+            println!("\n{}", paint(Style::new().dimmed(), &entry.help[..]));
+        }
+    }
+    Ok(())
+}
+
+/// How many units, headers and templates the time trace report shows of each.
+const TIME_TRACE_SHOWN: usize = 10;
+
+/// Reports where the compiler spent its time over all translation units of a configuration built
+/// with `-ftime-trace`, the most expensive headers and templates first.
+fn show_time_trace(matches: &Matches, project: &str, name: &str) -> Result<(), String> {
+    let settings = settings(matches, &[])?;
+    init_output(&settings);
+    let dir = config_dir(matches, project, name)?;
+    let report = time_trace::collect(&dir)?;
+    if report.units.is_empty() {
+        return Err(format!(
+            "{} has no time traces, build it with -ftime-trace first",
+            name
+        ));
+    }
+    let total: Duration = report.units.iter().map(|&(_, time)| time).sum();
+    if settings.json {
+        let costs = |costs: &[time_trace::Cost]| {
+            costs
+                .iter()
+                .map(|c| {
+                    json!({
+                        "name": c.name,
+                        "seconds": c.time.as_secs_f64(),
+                        "units": c.units,
+                    })
+                })
+                .collect::<Vec<_>>()
+        };
+        let units: Vec<_> = report
+            .units
+            .iter()
+            .map(|(unit, time)| json!({ "name": unit, "seconds": time.as_secs_f64() }))
+            .collect();
+        let report = json!({
+            "seconds": total.as_secs_f64(),
+            "units": units,
+            "headers": costs(&report.headers),
+            "templates": costs(&report.templates),
+        });
+        println!("{}", serde_json::to_string_pretty(&report).unwrap());
+        return Ok(());
+    }
+    println!(
+        "{} translation units took {}",
+        report.units.len(),
+        format_duration(total)
+    );
+    let title = "Slowest translation units";
+    println!("\n{}", paint(Style::new().bold(), title));
+    for (unit, time) in report.units.iter().take(TIME_TRACE_SHOWN) {
+        println!("  {:>7}  {}", format_duration(*time), unit);
+    }
+    let sections = [
+        ("Most expensive headers", &report.headers),
+        ("Most expensive template instantiations", &report.templates),
+    ];
+    for (title, costs) in sections {
+        if costs.is_empty() {
+            continue;
+        }
+        println!("\n{}", paint(Style::new().bold(), title));
+        for c in costs.iter().take(TIME_TRACE_SHOWN) {
+            println!(
+                "  {:>7}  {:>4}x  {}",
+                format_duration(c.time),
+                c.units,
+                c.name
+            );
+        }
+    }
+    Ok(())
+}
+
+/// What `env.sh` of the configuration in `dir` exports: the compilers cmake found, the build type
+/// and directory, the runtime options of its sanitizers and its own environment variables.
+fn config_environment(conf: &Config, dir: &Path) -> Vec<(String, String)> {
+    let build_dir = std::env::current_dir().unwrap().join(dir);
+    let build_dir = build_dir.display().to_string();
+    let mut vars = vec![("MKQCB_BUILD_DIR".to_string(), build_dir)];
+    let cache = CMakeCache::read(&dir.join("CMakeCache.txt")).ok();
+    let args = conf.compiler.as_cmake_args();
+    for (var, language) in [("CC", "C"), ("CXX", "CXX")] {
+        let key = format!("CMAKE_{}_COMPILER", language);
+        let prefix = format!("-D{}=", key);
+        let compiler = cache
+            .as_ref()
+            .and_then(|cache| cache.get(&key).map(str::to_string))
+            .or_else(|| {
+                args.iter()
+                    .find_map(|arg| arg.strip_prefix(&prefix[..]).map(str::to_string))
+            });
+        if let Some(compiler) = compiler {
+            vars.push((var.to_string(), compiler));
+        }
+    }
+    if let Some(build_type) = conf.build_type {
+        let build_type = build_type.name().to_string();
+        vars.push(("CMAKE_BUILD_TYPE".to_string(), build_type));
+    }
+    let used = sanitizers::from_args(&conf.cmake_args);
+    for &(sanitizer, var, options) in &sanitizers::RUNTIME_OPTIONS {
+        if used.iter().any(|s| s == sanitizer) {
+            vars.push((var.to_string(), options.to_string()));
+        }
+    }
+    for (key, value) in &conf.env {
+        vars.retain(|(k, _)| k != key);
+        vars.push((key.clone(), value.clone()));
+    }
+    vars
+}
+
+/// Points `compile_commands.json` in the project directory at that of a configuration.
+fn link_compile_commands(proj_dir: &Path, dir: &Path) -> Result<(), String> {
+    let target = dir.join("compile_commands.json");
+    let target = std::fs::canonicalize(&target)
+        .map_err(|e| format!("Failed to find {:?}: {}", target, e))?;
+    let link = proj_dir.join("compile_commands.json");
+    match std::fs::symlink_metadata(&link) {
+        Ok(ref meta) if meta.file_type().is_symlink() => std::fs::remove_file(&link)
+            .map_err(|e| format!("Failed to remove {:?}: {}", link, e))?,
+        Ok(_) => {
+            return Err(format!(
+                "{:?} exists and isn't a symlink, not replacing it",
+                link
+            ))
+        }
+        Err(_) => {}
+    }
+    log::info(&format!(
+        "Linking {} to {}",
+        link.display(),
+        target.display()
+    ));
+    #[cfg(unix)]
+    let result = std::os::unix::fs::symlink(&target, &link);
+    #[cfg(windows)]
+    let result = std::os::windows::fs::symlink_file(&target, &link);
+    result.map_err(|e| format!("Failed to link {:?} to {:?}: {}", link, target, e))
+}
+
+/// Unions the compile databases of a project's configurations into one in the build directory.
+///
+/// When several configurations compile the same file, the first one wins. Configurations are
+/// taken in the order of the `--configs` patterns they match.
+fn merge_compile_commands(matches: &Matches, arg: &str) -> Result<(), String> {
+    use serde_json::Value;
+    let layout = Layout::new(matches, arg)?;
+    let mut dirs = existing_configs(&layout)?;
+    let patterns = matches
+        .opt_str("configs")
+        .unwrap_or_else(|| "*".to_string());
+    let rank = |dir: &PathBuf| {
+        let name = file_name(dir);
+        patterns.split(',').position(|p| matches_pattern(p, &name))
+    };
+    dirs.retain(|dir| rank(dir).is_some());
+    dirs.sort_by_key(rank);
+    let mut seen = std::collections::HashSet::new();
+    let mut merged = Vec::new();
+    for dir in &dirs {
+        let path = dir.join("compile_commands.json");
+        let text = match std::fs::read_to_string(&path) {
+            Ok(text) => text,
+            Err(e) => {
+                warn(&format!("Skipping {}: {}", file_name(dir), e));
+                continue;
+            }
+        };
+        let entries: Vec<Value> =
+            serde_json::from_str(&text).map_err(|e| format!("Invalid {:?}: {}", path, e))?;
+        for entry in entries {
+            let directory = Path::new(entry["directory"].as_str().unwrap_or(""));
+            let file = directory.join(entry["file"].as_str().unwrap_or(""));
+            if seen.insert(file) {
+                merged.push(entry);
+            }
+        }
+    }
+    if merged.is_empty() {
+        return Err(format!("No compile commands found in {:?}", layout.root));
+    }
+    let out = layout.root.join("compile_commands.json");
+    let text = serde_json::to_string_pretty(&merged).unwrap() + "\n";
+    std::fs::write(&out, text).map_err(|e| format!("Failed to write {:?}: {}", out, e))?;
+    println!("Wrote {} entries to {}", merged.len(), out.display());
+    Ok(())
+}
+
+/// Asks a yes/no question on the terminal, defaulting to no.
+fn confirm(question: &str) -> bool {
+    print!("{} [y/N] ", question);
+    let _ = std::io::stdout().flush();
+    let mut answer = String::new();
+    if std::io::stdin().read_line(&mut answer).is_err() {
+        return false;
+    }
+    matches!(answer.trim(), "y" | "Y" | "yes")
+}
+
+/// Which sanitizer a configuration was set up with, going by its cache.
+fn cached_sanitizer(cache: &CMakeCache) -> Option<String> {
+    for key in ["SANITIZE", "ECM_ENABLE_SANITIZERS", "USE_SANITIZER"] {
+        if let Some(sanitize) = cache.get(key).filter(|s| !s.is_empty()) {
+            return Some(sanitize.to_lowercase());
+        }
+    }
+    let flags = cache
+        .get("CMAKE_CXX_FLAGS")
+        .or_else(|| cache.get("CMAKE_C_FLAGS"))?;
+    flags
+        .split_whitespace()
+        .find(|flag| flag.starts_with("-fsanitize="))
+        .map(|flag| flag["-fsanitize=".len()..].to_string())
+}
+
+fn modified(path: &Path) -> Option<std::time::SystemTime> {
+    std::fs::metadata(path).and_then(|m| m.modified()).ok()
+}
+
+/// Shows the existing configurations of a project, and whether they're out of date.
+fn list(matches: &Matches, arg: &str) -> Result<(), String> {
+    use ansi_term::Colour::{Green, Yellow};
+    init_output(&settings(matches, &[])?);
+    let proj_dir = resolve_project_dir(arg);
+    let layout = Layout::new(matches, arg)?;
+    let dirs = existing_configs(&layout)?;
+    if dirs.is_empty() {
+        return Err(format!("No configurations found in {:?}", layout.root));
+    }
+    let lists_modified = modified(&proj_dir.join("CMakeLists.txt"));
+    let width = dirs
+        .iter()
+        .map(|dir| file_name(dir).len())
+        .max()
+        .unwrap_or(0)
+        .max(13);
+    let header = format!(
+        "{:<width$}  {:<12}  {:<14}  {:<20}  {:<10}  Status",
+        "Configuration", "Compiler", "Build type", "Generator", "Sanitizer"
+    );
+    println!("{}", paint(Style::new().bold(), header));
+    for dir in &dirs {
+        let cache_path = dir.join("CMakeCache.txt");
+        let cache = CMakeCache::read(&cache_path)
+            .map_err(|e| format!("Failed to read {:?}: {}", cache_path, e))?;
+        let compiler = cache
+            .get("CMAKE_CXX_COMPILER")
+            .or_else(|| cache.get("CMAKE_C_COMPILER"))
+            .map(|path| file_name(Path::new(path)))
+            .unwrap_or_else(|| "-".to_string());
+        let stale = match (lists_modified, modified(&cache_path)) {
+            (Some(lists), Some(cache)) => lists > cache,
+            _ => false,
+        };
+        let status = if stale {
+            paint(Yellow.bold(), "stale")
+        } else {
+            paint(Green.normal(), "up to date")
+        };
+        println!(
+            "{:<width$}  {:<12}  {:<14}  {:<20}  {:<10}  {}",
+            file_name(dir),
+            compiler,
+            cache.get("CMAKE_BUILD_TYPE").unwrap_or("-"),
+            cache.get("CMAKE_GENERATOR").unwrap_or("-"),
+            cached_sanitizer(&cache).unwrap_or_else(|| "-".to_string()),
+            status
+        );
+    }
+    Ok(())
+}
+
+/// Deletes a project's configurations, or just some of them.
+///
+/// Once all are gone, so are the lockfile and the build directory, if nothing else is left in it.
+fn clean(matches: &Matches, arg: &str) -> Result<(), String> {
+    let layout = Layout::new(matches, arg)?;
+    if !layout.root.exists() {
+        return Err(format!("There is no build directory {:?}", layout.root));
+    }
+    let patterns = matches.opt_str("configs");
+    let entries = std::fs::read_dir(&layout.root)
+        .map_err(|e| format!("Failed to read build directory {:?}: {}", layout.root, e))?;
+    let mut targets: Vec<PathBuf> = entries
+        .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+        .filter(|path| path.is_dir() && layout.is_config_dir(&file_name(path)))
+        .filter(|path| match patterns {
+            Some(ref patterns) => matches_patterns(patterns, &file_name(path)),
+            None => true,
+        })
+        .collect();
+    targets.sort();
+    if let Some(ref patterns) = patterns {
+        if targets.is_empty() {
+            return Err(format!(
+                "No configurations in {:?} match {}",
+                layout.root, patterns
+            ));
+        }
+    }
+    let mut files = Vec::new();
+    if patterns.is_none() {
+        files.push(Lock::path(&layout.root));
+        files.push(layout.root.join(stats::FILE_NAME));
+        files.push(layout.root.join(reconfigure::FILE_NAME));
+        files.push(layout.root.join("compile_commands.json"));
+        files.retain(|file| file.is_file());
+    }
+    let list: Vec<String> = targets
+        .iter()
+        .chain(&files)
+        .map(|path| path.display().to_string())
+        .collect();
+    if list.is_empty() {
+        return Err(format!("There are no configurations in {:?}", layout.root));
+    }
+    if matches.opt_present("dry-run") {
+        for path in &list {
+            println!("Would delete {}", path);
+        }
+        return Ok(());
+    }
+    if !matches.opt_present("yes") && !confirm(&format!("Delete {}?", list.join(", "))) {
+        return Err("Nothing was deleted".to_string());
+    }
+    let worktrees = targets.iter().any(|dir| has_worktree(dir));
+    for dir in &targets {
+        log::info(&format!("Deleting {}", dir.display()));
+        std::fs::remove_dir_all(dir).map_err(|e| format!("Failed to delete {:?}: {}", dir, e))?;
+    }
+    if worktrees {
+        prune_worktrees(&resolve_project_dir(arg));
+    }
+    for file in &files {
+        std::fs::remove_file(file).map_err(|e| format!("Failed to delete {:?}: {}", file, e))?;
+    }
+    if patterns.is_none() {
+        let _ = std::fs::remove_dir(&layout.root);
+    }
+    Ok(())
+}
+
+fn generate(matches: &Matches, arg: &str, extra_args: &[String]) -> Result<(), String> {
+    let proj_dir = resolve_project_dir(arg);
+    if let Err(e) = std::fs::metadata(&proj_dir) {
+        return Err(format!(
+            "Error while trying to look up directory {:?}: {}",
+            proj_dir, e
+        ));
+    }
+    let props = parse_cmakelists_txt(&proj_dir)
+        .map_err(|e| format!("Failed to open CMakeLists.txt in {:?}: {}", proj_dir, e))?;
+    if matches.opt_present("list-options") {
+        list_options(&props);
+        return Ok(());
+    }
+    let file = ConfigFile::load(&proj_dir)?;
+    let only = only_patterns(matches, &file.profiles)?;
+    let interactive = matches.opt_present("interactive");
+    // Everything there is goes on the checklist, analyzers included
+    let everything = everything_patterns();
+    let planned_only = match only {
+        None if interactive => Some(&everything[..]),
+        ref only => only.as_deref(),
+    };
+    let mut configs = if matches.opt_present("from-presets") {
+        let mut configs = presets::configs(&proj_dir)?;
+        only_configs(planned_only, &mut configs)?;
+        customize_configs(matches, &mut configs)?;
+        configs
+    } else {
+        plan_configs(matches, &props, planned_only, &file.configs)?
+    };
+    let mut settings = settings(matches, extra_args)?;
+    load_project(&mut settings, &proj_dir)?;
+    init_output(&settings);
+    check_cmake(matches, &mut settings)?;
+    if interactive {
+        configs = pick_configs(configs, &settings, only.is_some())?;
+    }
+    let layout = Layout::new(matches, arg)?;
+    expand_args(&mut configs, &layout, &mut settings);
+    set_install_prefixes(&mut configs, &layout, &settings);
+    if settings.dashboard {
+        set_build_names(&mut configs);
+    }
+    if matches.opt_present("dry-run") {
+        for c in &configs {
+            println!("{} ({})", c.name, layout.dir(c).display());
+            let args = cmake_args(c, &settings, &proj_dir);
+            println!("  {}", shell_command_line(&settings.cmake, &args));
+        }
+        return Ok(());
+    }
+    create_tree(matches, &configs, &settings, &proj_dir, &layout)
+}
+
+/// Lets the user check the configurations to create off a list, then tells how to get the same
+/// without asking.
+///
+/// Those that can be created start out checked, except for analyzers and Bench configurations
+/// unless `all` is set.
+fn pick_configs(
+    configs: Vec<Config>,
+    settings: &Settings,
+    all: bool,
+) -> Result<Vec<Config>, String> {
+    let reasons: Vec<Option<String>> = configs
+        .iter()
+        .map(|c| missing_compilers_reason(c, settings))
+        .collect();
+    let is_extra = |c: &Config| {
+        opt_in_kinds()
+            .iter()
+            .any(|kind| c.name.ends_with(&format!("-{}", kind)))
+    };
+    let mut picked: Vec<bool> = configs
+        .iter()
+        .zip(&reasons)
+        .map(|(c, reason)| reason.is_none() && (all || !is_extra(c)))
+        .collect();
+    let width = configs.iter().map(|c| c.name.len()).max().unwrap_or(0);
+    loop {
+        println!("Pick the configurations to create:");
+        for (i, c) in configs.iter().enumerate() {
+            let mark = if picked[i] { "x" } else { " " };
+            let reason = reasons[i].as_ref().map_or("", |reason| &reason[..]);
+            let line = format!("  [{}] {:>2}  {:<width$}  {}", mark, i + 1, c.name, reason);
+            println!("{}", line.trim_end());
+        }
+        print!("Toggle by number (like 3 or 2-5), a for all, n for none, Enter to go on: ");
+        let _ = std::io::stdout().flush();
+        let mut answer = String::new();
+        match std::io::stdin().read_line(&mut answer) {
+            Ok(0) | Err(_) => {
+                println!();
+                return Err("Nothing picked".to_string());
+            }
+            Ok(_) => {}
+        }
+        let answer = answer.trim();
+        if answer.is_empty() {
+            if picked.contains(&true) {
+                break;
+            }
+            println!("Pick at least one.");
+            continue;
+        }
+        for word in answer.split([' ', ',']).filter(|w| !w.is_empty()) {
+            match word {
+                "a" => picked.iter_mut().for_each(|p| *p = true),
+                "n" => picked.iter_mut().for_each(|p| *p = false),
+                _ => match parse_range(word, configs.len()) {
+                    Some((first, last)) => {
+                        for p in &mut picked[first - 1..last] {
+                            *p = !*p;
+                        }
+                    }
+                    None => println!("Not a number from the list: {}", word),
+                },
+            }
+        }
+    }
+    let configs: Vec<Config> = configs
+        .into_iter()
+        .zip(picked)
+        .filter(|&(_, picked)| picked)
+        .map(|(c, _)| c)
+        .collect();
+    let names: Vec<&str> = configs.iter().map(|c| &c.name[..]).collect();
+    println!(
+        "Next time, this does the same:\n  {}",
+        equivalent_command(&names.join(","))
+    );
+    Ok(configs)
+}
+
+/// `3` or `2-5`, if it's within the `len` numbers of a list.
+fn parse_range(word: &str, len: usize) -> Option<(usize, usize)> {
+    let (first, last) = match word.split_once('-') {
+        Some((first, last)) => (first.parse().ok()?, last.parse().ok()?),
+        None => {
+            let n = word.parse().ok()?;
+            (n, n)
+        }
+    };
+    Some((first, last)).filter(|&(first, last)| first >= 1 && first <= last && last <= len)
+}
+
+/// The command line mkqcb was run with, but picking configurations with `--only only` instead of
+/// asking.
+fn equivalent_command(only: &str) -> String {
+    let mut args: Vec<String> = Vec::new();
+    let mut rest = std::env::args().skip(1);
+    while let Some(arg) = rest.next() {
+        if arg == "--" {
+            args.extend(["--only".to_string(), only.to_string(), arg]);
+            args.extend(rest.by_ref());
+            return shell_command_line("mkqcb", &args);
+        }
+        match &arg[..] {
+            "-i" | "--interactive" => {}
+            "--only" | "--profile" => {
+                rest.next();
+            }
+            _ if arg.starts_with("--only=") || arg.starts_with("--profile=") => {}
+            _ => args.push(arg),
+        }
+    }
+    args.extend(["--only".to_string(), only.to_string()]);
+    shell_command_line("mkqcb", &args)
+}
+
+/// Sets up each project given on the command line or in `--workspace` in turn.
+///
+/// One failing doesn't stop the others, how each went is summed up at the end.
+fn generate_projects(matches: &Matches, extra_args: &[String]) -> Result<(), String> {
+    let mut projects = matches.free.clone();
+    if let Some(manifest) = matches.opt_str("workspace") {
+        for project in config_file::workspace_projects(Path::new(&manifest))? {
+            let project = project
+                .into_os_string()
+                .into_string()
+                .map_err(|project| format!("{:?} isn't valid UTF-8", project))?;
+            projects.push(project);
+        }
+    }
+    if projects.is_empty() {
+        return Err("There are no projects to set up".to_string());
+    }
+    let mut results: Vec<ProjectResult> = Vec::new();
+    for project in &projects {
+        let root = Layout::new(matches, project)?.root;
+        if let Some(other) = results.iter().find(|r| r.root == root) {
+            return Err(format!(
+                "{} and {} would both go in {:?}, use --dir-template to tell them apart",
+                other.project, project, root
+            ));
+        }
+        results.push(ProjectResult {
+            project,
+            root,
+            result: None,
+        });
+    }
+    for r in &mut results {
+        if interrupted() {
+            break;
+        }
+        print_banner("Setting up project", r.project);
+        let result = generate(matches, r.project, extra_args);
+        if let Err(ref e) = result {
+            eprintln!("{}", e);
+        }
+        r.result = Some(result);
+    }
+    print_projects_summary(&results);
+    let failed: Vec<&str> = results
+        .iter()
+        .filter(|r| r.result.as_ref().is_some_and(|result| result.is_err()))
+        .map(|r| r.project)
+        .collect();
+    if interrupted() {
+        Err("Interrupted".to_string())
+    } else if failed.is_empty() {
+        Ok(())
+    } else {
+        Err(format!("Failed to set up {}", failed.join(", ")))
+    }
+}
+
+struct ProjectResult<'a> {
+    project: &'a str,
+    /// Where its configurations go
+    root: PathBuf,
+    /// None if it didn't get to run
+    result: Option<Result<(), String>>,
+}
+
+fn print_projects_summary(results: &[ProjectResult]) {
+    use ansi_term::Colour::{Green, Red, Yellow};
+    let width = results
+        .iter()
+        .map(|r| r.project.len())
+        .max()
+        .unwrap_or(0)
+        .max(7);
+    let mut out = progress_output();
+    let header = format!("{:<width$}  {:<10}  Directory", "Project", "Status");
+    let _ = writeln!(out, "\n{}", paint(Style::new().bold(), header));
+    for r in results {
+        let status = match r.result {
+            Some(Ok(())) => paint(Green.bold(), format!("{:<10}", "ok")),
+            Some(Err(_)) if interrupted() => paint(Red.bold(), format!("{:<10}", "stopped")),
+            Some(Err(_)) => paint(Red.bold(), format!("{:<10}", "failed")),
+            None => paint(Yellow.normal(), format!("{:<10}", "skipped")),
+        };
+        let _ = writeln!(
+            out,
+            "{:<width$}  {}  {}",
+            r.project,
+            status,
+            r.root.display()
+        );
+    }
+}
+
+/// Creates the configurations of `layout`, then builds and tests them if asked to.
+fn create_tree(
+    matches: &Matches,
+    configs: &[Config],
+    settings: &Settings,
+    proj_dir: &Path,
+    layout: &Layout,
+) -> Result<(), String> {
+    let mut existing = Vec::new();
+    let mut pending = Vec::new();
+    let mut dirs = std::collections::HashSet::new();
+    for c in configs {
+        let dir = layout.dir(c);
+        if !dirs.insert(dir.clone()) {
+            return Err(format!(
+                "--dir-template puts more than one configuration in {:?}",
+                dir
+            ));
+        }
+        if !dir.exists() {
+            pending.push(c);
+            continue;
+        }
+        if !matches.opt_present("resume") && !settings.fresh {
+            return Err(format!(
+                "The build directory ({:?}) already exists. Delete it first, or pass --resume \
+                 or --fresh.",
+                dir
+            ));
+        }
+        if settings.fresh && dir.join("CMakeCache.txt").is_file() {
+            pending.push(c);
+            continue;
+        }
+        if dir.join("CMakeCache.txt").is_file() {
+            existing.push(ConfigResult {
+                config: c,
+                model: fileapi::read_reply(&dir).ok().flatten(),
+                dir,
+                status: None,
+                duration: Duration::default(),
+                existing: true,
+                built: None,
+                build_duration: None,
+                tested: None,
+                command: None,
+            });
+            continue;
+        }
+        // Whatever is there didn't get as far as a cache, so it's redone from scratch
+        std::fs::remove_dir_all(&dir).map_err(|e| format!("Failed to remove {:?}: {}", dir, e))?;
+        pending.push(c);
+    }
+    let had_pending = !pending.is_empty();
+    skip_missing_compilers(&mut pending, settings);
+    if had_pending && pending.is_empty() && existing.is_empty() {
+        return Err(
+            "None of the configurations have their compilers installed, or supporting them"
+                .to_string(),
+        );
+    }
+    if let Some(ref remote) = settings.remote {
+        print_banner("Copying the source to", &remote.host);
+        remote.sync(proj_dir)?;
+    }
+    let mut usage = UsageHistory::load();
+    std::fs::create_dir_all(&layout.root)
+        .map_err(|e| format!("Failed to create {:?}: {}", layout.root, e))?;
+    if let Err(e) = check_destination(&layout.root, usage.estimate(pending.len())) {
+        let _ = std::fs::remove_dir(&layout.root);
+        return Err(e);
+    }
+    let mut stats = Stats::load(&layout.root);
+    let mut results = create_configs(&pending, layout, settings, proj_dir);
+    for r in &results {
+        if r.configured() {
+            usage.record(dir_size(&r.dir));
+        }
+    }
+    results.extend(existing);
+    results.sort_by_key(|r| configs.iter().position(|c| std::ptr::eq(c, r.config)));
+    usage.save();
+    // An interrupted configuration is only half done, so it goes regardless
+    if !settings.keep_failed || interrupted() {
+        remove_failed(&results, layout);
+        if settings.worktree.is_some() {
+            prune_worktrees(proj_dir);
+        }
+    }
+    if results.iter().any(|r| r.configured()) {
+        let configured = results.iter().filter(|r| r.configured()).map(|r| r.config);
+        let lock = Lock::new(configured, settings);
+        lock.write(&Lock::path(&layout.root))?;
+        let commands: Vec<(String, Command)> = results
+            .iter_mut()
+            .filter(|r| r.configured())
+            .map(|r| {
+                // Those of an earlier run get theirs without what the package managers added
+                let cmd = match r.command.take() {
+                    Some(cmd) => cmd,
+                    None => configure_command(r.config, &r.dir, settings, proj_dir),
+                };
+                (layout.dir_name(r.config), cmd)
+            })
+            .collect();
+        reconfigure::write(&layout.root, &commands)?;
+        for r in results.iter().filter(|r| r.configured()) {
+            let vars = config_environment(r.config, &r.dir);
+            env_script::write(&r.dir, &r.config.name, &vars)?;
+        }
+        if matches.opt_present("emit-presets") {
+            presets::export(proj_dir, layout, &lock.configs)?;
+        }
+        if matches.opt_present("qtcreator") {
+            qtcreator::write(proj_dir, layout, &lock.configs)?;
+        }
+    }
+    if interrupted() {
+        print_summary(configs, &results, layout, settings, &stats);
+        return Err("Interrupted".to_string());
+    }
+    let mut errors = Vec::new();
+    let failed: Vec<&str> = results
+        .iter()
+        .filter(|r| !r.configured())
+        .map(|r| &r.config.name[..])
+        .collect();
+    if !failed.is_empty() {
+        errors.push(format!("Failed to create {}", failed.join(", ")));
+    }
+    if settings.dashboard {
+        for r in results.iter_mut().filter(|r| r.configured()) {
+            if interrupted() {
+                break;
+            }
+            print_banner("Submitting to the dashboard", &r.config.name);
+            let start = Instant::now();
+            let submission = submit_config(&r.dir, settings);
+            r.built = Some(submission.built);
+            r.build_duration = Some(start.elapsed());
+            r.tested = Some(submission.tested).filter(|_| submission.built);
+            if !submission.built {
+                errors.push(format!("Failed to build {}", r.config.name));
+            }
+            if !submission.submitted {
+                errors.push(format!("Failed to submit {}", r.config.name));
+            }
+            let ok = submission.built && submission.tested && submission.submitted;
+            if !ok && !settings.keep_going {
+                break;
+            }
+        }
+        if results.iter().any(|r| r.tested == Some(false)) {
+            errors.push("Some tests failed".to_string());
+        }
+    } else if settings.build {
+        for r in results.iter_mut().filter(|r| r.configured()) {
+            if interrupted() {
+                break;
+            }
+            print_banner("Building configuration", &r.config.name);
+            let start = Instant::now();
+            let ok = build_config(&r.dir, settings);
+            r.built = Some(ok);
+            r.build_duration = Some(start.elapsed());
+            if !ok {
+                errors.push(format!("Failed to build {}", r.config.name));
+                if !settings.keep_going {
+                    break;
+                }
+            }
+        }
+    } else if let Some(jobs) = settings.launcher_jobs {
+        let _ = writeln!(
+            progress_output(),
+            "Build with -j{} to make use of the compile farm.",
+            jobs
+        );
+    }
+    let build_failed = results.iter().any(|r| r.built == Some(false));
+    if settings.test && !settings.dashboard && (!build_failed || settings.keep_going) {
+        let testable = results
+            .iter_mut()
+            .filter(|r| r.configured() && r.built != Some(false));
+        for r in testable {
+            if interrupted() {
+                break;
+            }
+            print_banner("Testing configuration", &r.config.name);
+            r.tested = Some(test_config(&r.dir, settings));
+        }
+        if results.iter().any(|r| r.tested == Some(false)) {
+            errors.push("Some tests failed".to_string());
+        }
+    }
+    if let Some(name) = matches.opt_str("link-compile-commands") {
+        match results.iter().find(|r| r.config.name == name) {
+            Some(r) if r.configured() => link_compile_commands(proj_dir, &r.dir)?,
+            Some(_) => errors.push(format!("Can't link {}, it failed", name)),
+            None => errors.push(format!("There is no configuration {} to link", name)),
+        }
+    }
+    print_summary(configs, &results, layout, settings, &stats);
+    let created: Vec<&ConfigResult> = results
+        .iter()
+        .filter(|r| r.configured() && !r.existing)
+        .collect();
+    for r in &created {
+        let timing = Timing {
+            configure: r.duration,
+            build: r.build_duration.filter(|_| r.built == Some(true)),
+        };
+        stats.record(&r.config.name, timing);
+    }
+    // Otherwise the build directory may well be gone
+    if !created.is_empty() {
+        stats.save()?;
+    }
+    if settings.json {
+        println!("{}", json_report(&results, settings, proj_dir));
+    }
+    if errors.is_empty() {
+        Ok(())
+    } else {
+        Err(errors.join("\n"))
+    }
+}
+
+/// Find the project in an unpacked tarball, which is usually inside a single top level directory.
+fn find_unpacked_project(dir: &Path) -> Option<PathBuf> {
+    if dir.join("CMakeLists.txt").is_file() {
+        return Some(dir.to_owned());
+    }
+    let mut candidates: Vec<PathBuf> = std::fs::read_dir(dir)
+        .ok()?
+        .filter_map(|e| e.ok())
+        .map(|e| e.path())
+        .filter(|p| p.join("CMakeLists.txt").is_file())
+        .collect();
+    if candidates.len() == 1 {
+        candidates.pop()
+    } else {
+        None
+    }
+}
+
+/// A directory of mkqcb's own in the temp dir, removed again when it goes out of scope.
+struct TempDir {
+    path: PathBuf,
+    /// Left in place after all, for a look at what went wrong
+    keep: bool,
+}
+
+impl Drop for TempDir {
+    fn drop(&mut self) {
+        if !self.keep {
+            let _ = std::fs::remove_dir_all(&self.path);
+        }
+    }
+}
+
+/// Unpack a source tarball somewhere temporary, then configure and build it in every
+/// selected configuration.
+fn from_tarball(matches: &Matches, tarball: &str, extra_args: &[String]) -> Result<(), String> {
+    use ansi_term::Colour::{Green, Red};
+    if matches.opt_present("dry-run") {
+        return Err("--dry-run is not supported with from-tarball".to_string());
+    }
+    let mut settings = settings(matches, extra_args)?;
+    if settings.json {
+        return Err("--output json is not supported with from-tarball".to_string());
+    }
+    init_output(&settings);
+    check_cmake(matches, &mut settings)?;
+    let tarball = canonicalize(tarball)
+        .map_err(|e| format!("Failed to find tarball {:?}: {}", tarball, e))?;
+    let mut tmp_dir = TempDir {
+        path: std::env::temp_dir().join(format!("mkqcb-{}", std::process::id())),
+        keep: false,
+    };
+    let src_dir = tmp_dir.path.join("src");
+    let build_dir = tmp_dir.path.join("build");
+    std::fs::create_dir_all(&src_dir)
+        .map_err(|e| format!("Failed to create {:?}: {}", src_dir, e))?;
+    let unpacked = Command::new("tar")
+        .arg("-xf")
+        .arg(&tarball)
+        .arg("-C")
+        .arg(&src_dir)
+        .status()
+        .map_err(|e| format!("Failed to run tar: {}", e))?;
+    if !unpacked.success() {
+        return Err(format!("Failed to unpack {:?}", tarball));
+    }
+    let proj_dir = match find_unpacked_project(&src_dir) {
+        Some(dir) => dir,
+        None => return Err(format!("Couldn't find a CMake project in {:?}", tarball)),
+    };
+    let props = parse_cmakelists_txt(&proj_dir)
+        .map_err(|e| format!("Failed to open CMakeLists.txt in {:?}: {}", proj_dir, e))?;
+    let file = ConfigFile::load(&proj_dir)?;
+    let only = only_patterns(matches, &file.profiles)?;
+    let planned = plan_configs(matches, &props, only.as_deref(), &file.configs)?;
+    settings.languages = Some(props.languages);
+    let mut configs: Vec<&Config> = planned.iter().collect();
+    skip_missing_compilers(&mut configs, &settings);
+    check_destination(&tmp_dir.path, UsageHistory::load().estimate(configs.len()))?;
+    std::fs::create_dir(&build_dir)
+        .map_err(|e| format!("Failed to create {:?}: {}", build_dir, e))?;
+    let mut results = Vec::new();
+    for c in configs {
+        if interrupted() {
+            return Err("Interrupted".to_string());
+        }
+        print_banner("Creating configuration for", &c.name);
+        let dir = build_dir.join(&c.name);
+        let (status, output, _) = create_config(c, &dir, &settings, &proj_dir, false);
+        let _ = std::io::stdout().write_all(&output);
+        let result = if !status.is_some_and(|s| s.success()) {
+            Err("configure failed")
+        } else if !build_config(&dir, &settings) {
+            Err("build failed")
+        } else if settings.test && !test_config(&dir, &settings) {
+            Err("tests failed")
+        } else {
+            Ok(())
+        };
+        results.push((&c.name, result));
+    }
+    println!("Results for {:?}:", tarball);
+    for &(name, ref result) in &results {
+        match *result {
+            Ok(()) => println!("  {:<24} {}", name, paint(Green.bold(), "ok")),
+            Err(msg) => println!("  {:<24} {}", name, paint(Red.bold(), msg)),
+        }
+    }
+    if results.iter().any(|(_, result)| result.is_err()) {
+        tmp_dir.keep = true;
+        return Err(format!(
+            "Not every configuration built. The unpacked tree is left in {:?}.",
+            tmp_dir.path
+        ));
+    }
+    Ok(())
+}
+
+/// The configurations of `project` there are, for completing their names.
+///
+/// There being none, or no project at all, is no reason to complain.
+fn complete_configs(matches: &Matches, project: &str) -> Result<(), String> {
+    let dirs = Layout::new(matches, project)
+        .and_then(|layout| existing_configs(&layout))
+        .unwrap_or_default();
+    for dir in dirs {
+        println!("{}", file_name(&dir));
+    }
+    Ok(())
+}
+
+/// The profiles `project` can use, for completing `--profile`.
+fn complete_profiles(project: &str) -> Result<(), String> {
+    let mut names: Vec<String> = PROFILES.iter().map(|&(name, _)| name.to_string()).collect();
+    if let Ok(file) = ConfigFile::load(&resolve_project_dir(project)) {
+        for (name, _) in file.profiles {
+            if !names.contains(&name) {
+                names.push(name);
+            }
+        }
+    }
+    for name in names {
+        println!("{}", name);
+    }
+    Ok(())
+}
+
+const SUBCOMMANDS: &[&str] = &[
+    "from-tarball",
+    "update",
+    "watch",
+    "clean",
+    "list",
+    "status",
+    "add",
+    "remove",
+    "regen",
+    "link",
+    "diff",
+    "cache",
+    "install",
+    "time-trace",
+    "ccdb",
+    "completions",
+];
+
+fn run() -> (i32, Option<String>) {
+    let mut args: Vec<String> = std::env::args().collect();
+    let program = args.remove(0);
+    // Everything after "--" goes to cmake as is
+    let extra_args = match args.iter().position(|arg| arg == "--") {
+        Some(pos) => {
+            let extra = args.split_off(pos + 1);
+            args.pop();
+            extra
+        }
+        None => Vec::new(),
+    };
+    // A second Ctrl-C gives up on cleaning up
+    let _ = ctrlc::set_handler(|| {
+        if INTERRUPTED.swap(true, Ordering::SeqCst) {
+            std::process::exit(130);
+        }
+    });
+    let opts = options();
+    let matches = match opts.parse(args) {
+        Ok(m) => m,
+        Err(e) => return (1, Some(format!("{}", e))),
+    };
+    log::set_verbosity(matches.opt_count("verbose"));
+    if matches.opt_present("h") {
+        print_usage(&program, &opts);
+        return (1, None);
+    }
+    let command = matches.free.first().map(|s| &s[..]);
+    let result = match (command, matches.free.get(1)) {
+        (Some("from-tarball"), Some(tarball)) => from_tarball(&matches, tarball, &extra_args),
+        (Some("update"), Some(project)) => update(&matches, project, &extra_args),
+        (Some("watch"), Some(project)) => watch(&matches, project, &extra_args),
+        (Some("clean"), Some(project)) => clean(&matches, project),
+        (Some("add"), Some(project)) => add(&matches, project, &extra_args),
+        (Some("remove"), Some(project)) => remove(&matches, project, &matches.free[2..]),
+        (Some("regen"), Some(project)) => regen(&matches, project),
+        (Some("ccdb"), Some(action)) => match (&action[..], matches.free.get(2)) {
+            ("merge", Some(project)) => merge_compile_commands(&matches, project),
+            _ => {
+                print_usage(&program, &opts);
+                return (1, None);
+            }
+        },
+        (Some("link"), Some(project)) => match matches.free.get(2) {
+            Some(name) => {
+                let proj_dir = resolve_project_dir(project);
+                Layout::new(&matches, project)
+                    .and_then(|layout| link_compile_commands(&proj_dir, &layout.root.join(name)))
+            }
+            None => {
+                print_usage(&program, &opts);
+                return (1, None);
+            }
+        },
+        (Some("diff"), Some(_)) => match matches.free[1..] {
+            [ref a, ref b] => diff_configs(&matches, ".", a, b),
+            [ref project, ref a, ref b] => diff_configs(&matches, project, a, b),
+            _ => {
+                print_usage(&program, &opts);
+                return (1, None);
+            }
+        },
+        (Some("install"), Some(_)) => match matches.free[1..] {
+            [ref name] => install(&matches, ".", name, &extra_args),
+            [ref project, ref name] => install(&matches, project, name, &extra_args),
+            _ => {
+                print_usage(&program, &opts);
+                return (1, None);
+            }
+        },
+        (Some("time-trace"), Some(_)) => match matches.free[1..] {
+            [ref name] => show_time_trace(&matches, ".", name),
+            [ref project, ref name] => show_time_trace(&matches, project, name),
+            _ => {
+                print_usage(&program, &opts);
+                return (1, None);
+            }
+        },
+        (Some("cache"), Some(_)) => match matches.free[1..] {
+            [ref name] => show_cache(&matches, ".", name, None),
+            // The project can be left out, then there's a pattern
+            [ref project, ref name] if config_dir(&matches, project, name).is_ok() => {
+                show_cache(&matches, project, name, None)
+            }
+            [ref name, ref pattern] => show_cache(&matches, ".", name, Some(pattern)),
+            [ref project, ref name, ref pattern] => {
+                show_cache(&matches, project, name, Some(pattern))
+            }
+            _ => {
+                print_usage(&program, &opts);
+                return (1, None);
+            }
+        },
+        (Some("list"), Some(project)) | (Some("status"), Some(project)) => list(&matches, project),
+        (Some("completions"), Some(what)) => {
+            let project = matches.free.get(2).map_or(".", |s| &s[..]);
+            match &what[..] {
+                "configs" => complete_configs(&matches, project),
+                "profiles" => complete_profiles(project),
+                shell => match completions::script(shell, &opts, SUBCOMMANDS) {
+                    Some(script) => {
+                        print!("{}", script);
+                        Ok(())
+                    }
+                    None => Err(format!("There are no completions for {}", shell)),
+                },
+            }
+        }
+        (Some(command), None) if SUBCOMMANDS.contains(&command) => {
+            print_usage(&program, &opts);
+            return (1, None);
+        }
+        _ if matches.free.len() > 1 || matches.opt_present("workspace") => {
+            generate_projects(&matches, &extra_args)
+        }
+        (Some(arg), _) => generate(&matches, arg, &extra_args),
+        (None, _) => {
+            print_usage(&program, &opts);
+            return (1, None);
+        }
+    };
+    match result {
+        Ok(()) => (0, None),
+        Err(e) if interrupted() => (130, Some(e)),
+        Err(e) => (1, Some(e)),
     }
-
-    (0, None)
 }
 
 fn main() {
@@ -232,3 +5380,282 @@ fn main() {
     }
     std::process::exit(retv);
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn patterns() {
+        assert!(matches_pattern("GCC-Debug", "GCC-Debug"));
+        assert!(!matches_pattern("GCC-Debug", "GCC-Debug2"));
+        assert!(matches_pattern("*", "Clang-Asan"));
+        assert!(matches_pattern("*-Asan", "Clang-Asan"));
+        assert!(!matches_pattern("*-Asan", "Clang-Asan-Old"));
+        assert!(matches_pattern("Clang-*", "Clang-"));
+        assert!(matches_pattern("*a*a*", "banana"));
+        assert!(!matches_pattern("*an*na*x", "banana"));
+        assert!(!matches_pattern("ab*ba", "aba"));
+        assert!(matches_patterns("GCC-*,*-Tsan", "Clang-Tsan"));
+        assert!(!matches_patterns("GCC-*,*-Tsan", "Clang-Asan"));
+    }
+
+    #[test]
+    fn policies() {
+        assert_eq!(
+            policy_arg("CMP0077=NEW"),
+            Ok("-DCMAKE_POLICY_DEFAULT_CMP0077=NEW".to_string())
+        );
+        assert!(policy_arg("CMP0077=OLD").is_ok());
+        let invalid = [
+            "CMP0077",
+            "CMP0077=new",
+            "CMP77=NEW",
+            "CMPabcd=NEW",
+            "XYZ0077=NEW",
+        ];
+        for policy in invalid {
+            assert!(policy_arg(policy).is_err(), "{}", policy);
+        }
+    }
+
+    #[test]
+    fn remotes() {
+        let remote = Remote::parse("user@host:/home/user/builds").unwrap();
+        assert_eq!(remote.host, "user@host");
+        assert_eq!(remote.path, Path::new("/home/user/builds"));
+        assert_eq!(
+            remote.dir(Path::new("build-proj/GCC-Debug")),
+            Path::new("/home/user/builds/build-proj/GCC-Debug")
+        );
+        for spec in ["host", "host:relative", ":/path", "host:"] {
+            assert!(Remote::parse(spec).is_err(), "{}", spec);
+        }
+    }
+
+    #[test]
+    fn ranges() {
+        assert_eq!(parse_range("3", 5), Some((3, 3)));
+        assert_eq!(parse_range("2-5", 5), Some((2, 5)));
+        assert_eq!(parse_range("0", 5), None);
+        assert_eq!(parse_range("6", 5), None);
+        assert_eq!(parse_range("4-2", 5), None);
+        assert_eq!(parse_range("2-6", 5), None);
+        assert_eq!(parse_range("a-b", 5), None);
+        assert_eq!(parse_range("-3", 5), None);
+    }
+
+    #[test]
+    fn languages() {
+        let languages = |text| project_languages(&cmakelists::parse(text));
+        assert_eq!(languages("project(foo)"), ["C", "CXX"]);
+        assert_eq!(languages("project(foo CXX)"), ["CXX"]);
+        assert_eq!(languages("project(foo VERSION 1.0)"), ["C", "CXX"]);
+        assert_eq!(
+            languages("project(foo VERSION 1.0 LANGUAGES c Fortran DESCRIPTION bar)"),
+            ["C", "FORTRAN"]
+        );
+        assert_eq!(
+            languages("project(foo NONE)\nenable_language(CUDA OPTIONAL)"),
+            ["CUDA"]
+        );
+        assert_eq!(languages("enable_language(CUDA)"), ["CUDA", "C", "CXX"]);
+        assert_eq!(
+            languages("project(foo C CXX)\nenable_language(C)"),
+            ["C", "CXX"]
+        );
+    }
+
+    #[test]
+    fn subdirectories() {
+        let root = TempDir {
+            path: std::env::temp_dir().join(format!("mkqcb-commands-{}", std::process::id())),
+            keep: false,
+        };
+        let scripts = [
+            (
+                "",
+                "project(foo)\n\
+                 add_subdirectory(lib)\n\
+                 add_subdirectory(${DIR})\n\
+                 add_subdirectory(lib/../other)\n\
+                 add_subdirectory(missing)",
+            ),
+            ("lib", "enable_language(CUDA)\nadd_subdirectory(inner)"),
+            ("lib/inner", "enable_language(Fortran)"),
+            // Nothing gets evaluated, so neither of those get read
+            ("${DIR}", "enable_language(ASM)"),
+            ("other", "enable_language(ASM_NASM)"),
+        ];
+        for (dir, text) in scripts {
+            let dir = root.path.join(dir);
+            std::fs::create_dir_all(&dir).unwrap();
+            std::fs::write(dir.join("CMakeLists.txt"), text).unwrap();
+        }
+        let mut commands = Vec::new();
+        read_commands(&root.path, &mut commands).unwrap();
+        assert_eq!(commands.len(), 8);
+        let languages = project_languages(&commands);
+        assert_eq!(languages, ["C", "CXX", "CUDA", "FORTRAN"]);
+        assert!(read_commands(&root.path.join("missing"), &mut Vec::new()).is_err());
+    }
+
+    fn spec(name: &str, extends: Option<&str>) -> ConfigSpec {
+        ConfigSpec {
+            name: name.to_string(),
+            extends: extends.map(|base| base.to_string()),
+            ..ConfigSpec::default()
+        }
+    }
+
+    #[test]
+    fn extending() {
+        let configs = [config("Debug", Clang, Debug, &["-DA=1"])];
+        let mut base = spec("Base", Some("Clang-Debug"));
+        base.is_abstract = true;
+        base.args = vec!["-DB=2".to_string()];
+        base.env = vec![("CFLAGS".to_string(), "-O1".to_string())];
+        let mut fast = spec("Fast", Some("Base"));
+        fast.build_type = Some("release".to_string());
+        fast.args = vec!["-DC=3".to_string()];
+        fast.env = vec![("CFLAGS".to_string(), "-O2".to_string())];
+        let mut own = spec("Own", None);
+        own.compiler = Some("gcc".to_string());
+        let resolved = resolve_specs(&[base, fast, own], &configs).unwrap();
+        assert_eq!(resolved.len(), 2);
+        let fast = &resolved[0];
+        assert_eq!(fast.name, "Fast");
+        assert_eq!(fast.compiler.to_string(), "Clang");
+        assert_eq!(fast.build_type.map(|b| b.name()), Some("Release"));
+        assert_eq!(fast.cmake_args, ["-DA=1", "-DB=2", "-DC=3"]);
+        assert_eq!(fast.env, [("CFLAGS".to_string(), "-O2".to_string())]);
+        assert_eq!(resolved[1].compiler.to_string(), "GCC");
+    }
+
+    #[test]
+    fn extension_errors() {
+        let cycle = [
+            spec("A", Some("B")),
+            spec("B", Some("C")),
+            spec("C", Some("A")),
+        ];
+        let error = resolve_specs(&cycle, &[]).err().unwrap();
+        assert!(error.ends_with("A -> B -> C -> A"), "{}", error);
+        assert!(resolve_specs(&[spec("A", Some("A"))], &[]).is_err());
+        assert!(resolve_specs(&[spec("A", Some("Nope"))], &[]).is_err());
+        assert!(resolve_specs(&[spec("A", None)], &[]).is_err());
+        let mut generator = spec("A", None);
+        generator.compiler = Some("gcc".to_string());
+        generator.generator = Some("Nope".to_string());
+        assert!(resolve_specs(&[generator], &[]).is_err());
+    }
+
+    fn layout(args: &[&str]) -> Result<Layout, String> {
+        let args = args.iter().map(|arg| arg.to_string()).collect();
+        Layout::new(&options().parse(args).unwrap(), "proj")
+    }
+
+    #[test]
+    fn layouts() {
+        let conf = config("Release", Gcc, Release, &[]);
+        let default = layout(&[]).unwrap();
+        assert_eq!(default.dir(&conf), Path::new("build-proj/GCC-Release"));
+        assert!(default.is_config_dir("Clang-Asan"));
+        let custom = layout(&["--dir-template", "out/{project}/{compiler}_{name}"]).unwrap();
+        assert_eq!(custom.root, Path::new("out/proj"));
+        assert_eq!(custom.dir_name(&conf), "GCC_Release");
+        assert!(custom.is_config_dir("Clang_Asan"));
+        assert!(!custom.is_config_dir("Clang-Asan"));
+        let output = layout(&["--output-dir", "/tmp", "--dir-template", "{config}/"]).unwrap();
+        assert_eq!(output.dir(&conf), Path::new("/tmp/GCC-Release"));
+        let invalid = [
+            "build/{nope}",
+            "build/{config",
+            "{config}/build",
+            "build-{project}",
+            "/",
+        ];
+        for template in invalid {
+            let args = ["--dir-template", template];
+            assert!(layout(&args).is_err(), "{}", template);
+        }
+    }
+
+    #[test]
+    fn placeholders() {
+        let conf = config("Asan", Clang, Debug, &[]);
+        let build_dir = Path::new("/builds/Clang-Asan");
+        let expand = |template| expand_placeholders(template, &conf, "foo", build_dir);
+        assert_eq!(expand("{project}-{config}"), "foo-Clang-Asan");
+        assert_eq!(expand("{compiler}/{name}/{type}"), "Clang/Asan/Debug");
+        assert_eq!(expand("{build_type}"), "Debug");
+        assert_eq!(expand("{build_dir}/out"), "/builds/Clang-Asan/out");
+        assert_eq!(expand("{Config} {unknown} {"), "{Config} {unknown} {");
+        let mut xcode = config("Xcode", Clang, Debug, &[]);
+        xcode.build_type = None;
+        let default = expand_placeholders("{type}", &xcode, "foo", build_dir);
+        assert_eq!(default, "Default");
+    }
+
+    #[test]
+    fn opt_in_configs() {
+        let matches = options().parse(Vec::<String>::new()).unwrap();
+        let props = CMakeListsProperties {
+            sanitizers: None,
+            languages: vec!["CXX".to_string()],
+            options: Vec::new(),
+        };
+        let names = |only| {
+            let configs = plan_configs(&matches, &props, Some(only), &[]).unwrap();
+            configs.into_iter().map(|c| c.name).collect::<Vec<_>>()
+        };
+        let all = names("*");
+        assert!(all.contains(&"Clang-Debug".to_string()));
+        for kind in opt_in_kinds() {
+            let suffix = format!("-{}", kind);
+            assert!(!all.iter().any(|name| name.ends_with(&suffix)), "{}", kind);
+        }
+        assert!(names("Clang-*").iter().all(|name| !name.ends_with("-Tidy")));
+        assert_eq!(names("*-Tidy"), ["Clang-Tidy"]);
+        assert_eq!(names("Clang-Valgrind,Clang-Debug").len(), 2);
+    }
+
+    fn pools(args: &[&str]) -> Result<Vec<(String, usize)>, String> {
+        let args = args.iter().map(|arg| arg.to_string()).collect();
+        job_pools(&options().parse(args).unwrap(), None)
+    }
+
+    #[test]
+    fn job_pool_options() {
+        let declared = pools(&["--job-pool", "heavy=2", "--link-jobs", "3"]).unwrap();
+        assert_eq!(
+            job_pool_args(&declared),
+            [
+                "-DCMAKE_JOB_POOLS=heavy=2;link=3",
+                "-DCMAKE_JOB_POOL_LINK=link"
+            ]
+        );
+        let overridden = pools(&["--job-pool", "link=1", "--link-jobs", "4"]).unwrap();
+        assert_eq!(overridden, [("link".to_string(), 4)]);
+        assert!(job_pool_args(&pools(&[]).unwrap()).is_empty());
+    }
+
+    #[test]
+    fn invalid_job_pools() {
+        let invalid = [
+            "heavy",
+            "heavy=0",
+            "heavy=two",
+            "heavy=",
+            "=2",
+            "a;b=2",
+            "heavy=-1",
+        ];
+        for pool in invalid {
+            assert!(pools(&["--job-pool", pool]).is_err(), "{}", pool);
+        }
+        for jobs in ["0", "many"] {
+            assert!(pools(&["--link-jobs", jobs]).is_err(), "{}", jobs);
+        }
+    }
+}
@@ -0,0 +1,567 @@
+//! Exporting configurations to `CMakeUserPresets.json`, and reading them from `CMakePresets.json`.
+
+use super::lock::env_json;
+use super::{
+    file_name, warn, BuildSystem, BuildType, Compiler, Config, Layout, EXPORT_COMPILE_COMMANDS_ARG,
+};
+use serde_json::{Map, Value};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+
+pub const FILE_NAME: &str = "CMakeUserPresets.json";
+
+/// Presets we generate have this prefix, everything else in the file is left alone.
+const PREFIX: &str = "mkqcb-";
+
+fn is_ours(preset: &Value) -> bool {
+    preset["name"]
+        .as_str()
+        .is_some_and(|name| name.starts_with(PREFIX))
+}
+
+fn read(proj_dir: &Path) -> Result<Option<Value>, String> {
+    let path = proj_dir.join(FILE_NAME);
+    match std::fs::read_to_string(&path) {
+        Ok(text) => serde_json::from_str(&text)
+            .map(Some)
+            .map_err(|e| format!("Invalid {:?}: {}", path, e)),
+        Err(ref e) if e.kind() == std::io::ErrorKind::NotFound => Ok(None),
+        Err(e) => Err(format!("Failed to read {:?}: {}", path, e)),
+    }
+}
+
+/// Whether the project has presets exported by us that need keeping up to date.
+pub fn exported(proj_dir: &Path) -> bool {
+    match read(proj_dir) {
+        Ok(Some(presets)) => presets["configurePresets"]
+            .as_array()
+            .is_some_and(|presets| presets.iter().any(is_ours)),
+        _ => false,
+    }
+}
+
+/// A configure preset equivalent to `conf`, which lives in `dir`.
+fn preset(conf: &Config, dir: &Path) -> Value {
+    let mut cache_variables = Map::new();
+    let mut warnings = Map::new();
+    let mut args = vec![EXPORT_COMPILE_COMMANDS_ARG.to_string()];
+    args.extend(conf.compiler.as_cmake_args());
+    if let Some(build_type) = conf.build_type {
+        args.push(build_type.as_cmake_arg().to_string());
+    }
+    args.extend(conf.cmake_args.iter().cloned());
+    for arg in &args {
+        if let Some(define) = arg.strip_prefix("-D") {
+            let (key, value) = match define.find('=') {
+                Some(pos) => (&define[..pos], &define[pos + 1..]),
+                None => (define, ""),
+            };
+            match key.find(':') {
+                Some(pos) => {
+                    let entry = json!({"type": &key[pos + 1..], "value": value});
+                    cache_variables.insert(key[..pos].to_string(), entry)
+                }
+                None => cache_variables.insert(key.to_string(), Value::from(value)),
+            };
+            continue;
+        }
+        let warning = match &arg[..] {
+            "-Wdev" => "dev",
+            "-Wdeprecated" => "deprecated",
+            "--warn-uninitialized" => "uninitialized",
+            _ => {
+                warn(&format!("Leaving {} out of the presets", arg));
+                continue;
+            }
+        };
+        warnings.insert(warning.to_string(), Value::Bool(true));
+    }
+    let mut preset = json!({
+        "name": format!("{}{}", PREFIX, conf.name),
+        "displayName": format!("{} (mkqcb)", conf.name),
+        "binaryDir": dir,
+        "cacheVariables": cache_variables,
+    });
+    if !conf.env.is_empty() {
+        preset["environment"] = env_json(&conf.env);
+    }
+    if let Some(build_system) = conf.build_system {
+        preset["generator"] = Value::from(build_system.generator());
+    }
+    if !warnings.is_empty() {
+        preset["warnings"] = Value::Object(warnings);
+    }
+    preset
+}
+
+/// Replaces the presets we exported earlier with ones for `configs`.
+pub fn export(proj_dir: &Path, layout: &Layout, configs: &[Config]) -> Result<(), String> {
+    let root = super::canonicalize(&layout.root)
+        .map_err(|e| format!("Failed to find {:?}: {}", layout.root, e))?;
+    let mut presets = read(proj_dir)?.unwrap_or_else(|| json!({"version": 3}));
+    let mut configure_presets: Vec<Value> = match presets["configurePresets"].as_array() {
+        Some(existing) => existing.iter().filter(|p| !is_ours(p)).cloned().collect(),
+        None => Vec::new(),
+    };
+    configure_presets.extend(
+        configs
+            .iter()
+            .map(|c| preset(c, &root.join(layout.dir_name(c)))),
+    );
+    presets["configurePresets"] = Value::Array(configure_presets);
+    let path = proj_dir.join(FILE_NAME);
+    let text = serde_json::to_string_pretty(&presets).unwrap() + "\n";
+    std::fs::write(&path, text).map_err(|e| format!("Failed to write {:?}: {}", path, e))
+}
+
+/// What macros in a preset expand to.
+struct Macros<'a> {
+    source_dir: &'a Path,
+    file_dir: &'a Path,
+    preset_name: &'a str,
+    generator: &'a str,
+    env: &'a [(String, String)],
+}
+
+impl<'a> Macros<'a> {
+    fn env(&self, name: &str) -> String {
+        match self.env.iter().find(|(key, _)| key == name) {
+            Some((_, value)) => value.clone(),
+            None => std::env::var(name).unwrap_or_default(),
+        }
+    }
+    fn lookup(&self, namespace: &str, name: &str) -> Option<String> {
+        let path = |p: &Path| Some(p.to_string_lossy().into_owned());
+        match (namespace, name) {
+            ("", "sourceDir") => path(self.source_dir),
+            ("", "sourceParentDir") => path(self.source_dir.parent().unwrap_or(self.source_dir)),
+            ("", "sourceDirName") => self
+                .source_dir
+                .file_name()
+                .map(|name| name.to_string_lossy().into_owned()),
+            ("", "fileDir") => path(self.file_dir),
+            ("", "presetName") => Some(self.preset_name.to_string()),
+            ("", "generator") => Some(self.generator.to_string()),
+            ("", "hostSystemName") => Some(
+                match std::env::consts::OS {
+                    "linux" => "Linux",
+                    "macos" => "Darwin",
+                    "windows" => "Windows",
+                    other => other,
+                }
+                .to_string(),
+            ),
+            ("", "dollar") => Some("$".to_string()),
+            ("", "pathListSep") => Some(if cfg!(windows) { ";" } else { ":" }.to_string()),
+            ("env", name) => Some(self.env(name)),
+            ("penv", name) => Some(std::env::var(name).unwrap_or_default()),
+            _ => None,
+        }
+    }
+    /// Expands `${name}`, `$env{name}` and `$penv{name}`, leaving anything unknown as is.
+    fn expand(&self, text: &str) -> String {
+        let mut out = String::new();
+        let mut rest = text;
+        while let Some(start) = rest.find('$') {
+            out.push_str(&rest[..start]);
+            rest = &rest[start..];
+            let expansion = rest.find('{').and_then(|open| {
+                let namespace = &rest[1..open];
+                let close = rest[open..].find('}')? + open;
+                let value = self.lookup(namespace, &rest[open + 1..close])?;
+                Some((value, close + 1))
+            });
+            match expansion {
+                Some((value, len)) => {
+                    out.push_str(&value);
+                    rest = &rest[len..];
+                }
+                None => {
+                    out.push('$');
+                    rest = &rest[1..];
+                }
+            }
+        }
+        out.push_str(rest);
+        out
+    }
+    fn condition_holds(&self, condition: &Value) -> bool {
+        let string = |key: &str| self.expand(condition[key].as_str().unwrap_or(""));
+        let in_list = || {
+            let needle = string("string");
+            condition["list"].as_array().is_some_and(|list| {
+                list.iter()
+                    .any(|s| s.as_str().map(|s| self.expand(s)) == Some(needle.clone()))
+            })
+        };
+        let conditions = || {
+            condition["conditions"]
+                .as_array()
+                .cloned()
+                .unwrap_or_default()
+        };
+        match condition["type"].as_str() {
+            Some("const") => condition["value"].as_bool().unwrap_or(true),
+            Some("equals") => string("lhs") == string("rhs"),
+            Some("notEquals") => string("lhs") != string("rhs"),
+            Some("inList") => in_list(),
+            Some("notInList") => !in_list(),
+            Some("anyOf") => conditions().iter().any(|c| self.condition_holds(c)),
+            Some("allOf") => conditions().iter().all(|c| self.condition_holds(c)),
+            Some("not") => !self.condition_holds(&condition["condition"]),
+            // Regular expressions aren't worth the dependency
+            _ => true,
+        }
+    }
+}
+
+/// Reads the configure presets of a presets file and the ones it includes.
+fn load(
+    path: &Path,
+    presets: &mut Vec<(Value, PathBuf)>,
+    seen: &mut Vec<PathBuf>,
+) -> Result<(), String> {
+    let path =
+        std::fs::canonicalize(path).map_err(|e| format!("Failed to find {:?}: {}", path, e))?;
+    if seen.contains(&path) {
+        return Ok(());
+    }
+    seen.push(path.clone());
+    let text =
+        std::fs::read_to_string(&path).map_err(|e| format!("Failed to read {:?}: {}", path, e))?;
+    let file: Value =
+        serde_json::from_str(&text).map_err(|e| format!("Invalid {:?}: {}", path, e))?;
+    let dir = path.parent().unwrap().to_path_buf();
+    if let Some(includes) = file["include"].as_array() {
+        for include in includes.iter().filter_map(|i| i.as_str()) {
+            load(&dir.join(include), presets, seen)?;
+        }
+    }
+    if let Some(configure_presets) = file["configurePresets"].as_array() {
+        for preset in configure_presets {
+            presets.push((preset.clone(), dir.clone()));
+        }
+    }
+    Ok(())
+}
+
+fn parents(preset: &Value) -> Vec<&str> {
+    match preset["inherits"] {
+        Value::String(ref parent) => vec![&parent[..]],
+        Value::Array(ref parents) => parents.iter().filter_map(|p| p.as_str()).collect(),
+        _ => Vec::new(),
+    }
+}
+
+/// `name` with everything it inherits filled in.
+fn resolve(
+    name: &str,
+    all: &HashMap<&str, &Value>,
+    depth: usize,
+) -> Result<Map<String, Value>, String> {
+    let preset = match all.get(name) {
+        Some(preset) => preset,
+        None => return Err(format!("Unknown preset {:?}", name)),
+    };
+    if depth > 32 {
+        return Err(format!("Preset {:?} inherits from itself", name));
+    }
+    let mut resolved = preset.as_object().cloned().unwrap_or_default();
+    resolved.remove("inherits");
+    for parent in parents(preset) {
+        for (key, value) in resolve(parent, all, depth + 1)? {
+            if key == "hidden" {
+                continue;
+            }
+            match (resolved.get_mut(&key), value) {
+                // Earlier parents win for every variable, but the preset itself wins overall
+                (Some(&mut Value::Object(ref mut own)), Value::Object(inherited))
+                    if key == "cacheVariables" || key == "environment" =>
+                {
+                    for (k, v) in inherited {
+                        own.entry(k).or_insert(v);
+                    }
+                }
+                (Some(_), _) => {}
+                (None, value) => {
+                    resolved.insert(key, value);
+                }
+            }
+        }
+    }
+    Ok(resolved)
+}
+
+fn cache_value(value: &Value, macros: &Macros) -> Option<String> {
+    match *value {
+        Value::String(ref s) => Some(macros.expand(s)),
+        Value::Bool(b) => Some(if b { "TRUE" } else { "FALSE" }.to_string()),
+        _ => None,
+    }
+}
+
+/// The configuration equivalent to a resolved preset, or `None` if it can't be created.
+fn config(
+    preset: &Map<String, Value>,
+    file_dir: &Path,
+    source_dir: &Path,
+) -> Result<Option<Config>, String> {
+    let name = match preset.get("name").and_then(|n| n.as_str()) {
+        Some(name) => name.to_string(),
+        None => return Err("Found a configure preset without a name".to_string()),
+    };
+    let mut macros = Macros {
+        source_dir,
+        file_dir,
+        preset_name: &name,
+        generator: preset
+            .get("generator")
+            .and_then(|g| g.as_str())
+            .unwrap_or(""),
+        env: &[],
+    };
+    let mut env = Vec::new();
+    if let Some(vars) = preset.get("environment").and_then(|e| e.as_object()) {
+        for (key, value) in vars {
+            if let Some(value) = value.as_str() {
+                env.push((key.clone(), macros.expand(value)));
+            }
+        }
+    }
+    macros.env = &env;
+    if let Some(condition) = preset.get("condition") {
+        if !macros.condition_holds(condition) {
+            return Ok(None);
+        }
+    }
+    let build_system = match preset.get("generator").and_then(|g| g.as_str()) {
+        Some(generator) => match BuildSystem::from_generator(generator) {
+            Some(build_system) => Some(build_system),
+            None => {
+                warn(&format!(
+                    "Skipping preset {}, the {} generator isn't supported",
+                    name, generator
+                ));
+                return Ok(None);
+            }
+        },
+        None => None,
+    };
+    let mut build_type = None;
+    let mut c = None;
+    let mut cxx = None;
+    let mut toolchain = preset
+        .get("toolchainFile")
+        .and_then(|t| t.as_str())
+        .map(|t| source_dir.join(macros.expand(t)));
+    let mut cmake_args = Vec::new();
+    if let Some(vars) = preset.get("cacheVariables").and_then(|v| v.as_object()) {
+        for (key, value) in vars {
+            let (type_, value) = match *value {
+                Value::Object(ref entry) => {
+                    (entry.get("type").and_then(|t| t.as_str()), &entry["value"])
+                }
+                ref value => (None, value),
+            };
+            let value = match cache_value(value, &macros) {
+                Some(value) => value,
+                None => continue,
+            };
+            match &key[..] {
+                "CMAKE_BUILD_TYPE" if BuildType::from_name(&value).is_ok() => {
+                    build_type = BuildType::from_name(&value).ok()
+                }
+                "CMAKE_C_COMPILER" => c = Some(value),
+                "CMAKE_CXX_COMPILER" => cxx = Some(value),
+                "CMAKE_TOOLCHAIN_FILE" => toolchain = Some(source_dir.join(value)),
+                _ => match type_ {
+                    Some(type_) => cmake_args.push(format!("-D{}:{}={}", key, type_, value)),
+                    None => cmake_args.push(format!("-D{}={}", key, value)),
+                },
+            }
+        }
+    }
+    let flag = |group: &str, key: &str| preset.get(group).and_then(|g| g[key].as_bool());
+    let warning_flags = [
+        (flag("warnings", "dev"), "-Wdev", "-Wno-dev"),
+        (
+            flag("warnings", "deprecated"),
+            "-Wdeprecated",
+            "-Wno-deprecated",
+        ),
+        (
+            flag("warnings", "uninitialized"),
+            "--warn-uninitialized",
+            "",
+        ),
+        (flag("warnings", "unusedCli"), "", "--no-warn-unused-cli"),
+        (flag("warnings", "systemVars"), "--check-system-vars", ""),
+        (flag("errors", "dev"), "-Werror=dev", "-Wno-error=dev"),
+        (
+            flag("errors", "deprecated"),
+            "-Werror=deprecated",
+            "-Wno-error=deprecated",
+        ),
+    ];
+    for &(value, on, off) in &warning_flags {
+        let arg = match value {
+            Some(true) => on,
+            Some(false) => off,
+            None => "",
+        };
+        if !arg.is_empty() {
+            cmake_args.push(arg.to_string());
+        }
+    }
+    let compiler = match toolchain {
+        Some(file) => {
+            // The toolchain file usually picks the compilers, but the preset gets the last word
+            let compilers = [("CMAKE_C_COMPILER", c), ("CMAKE_CXX_COMPILER", cxx)];
+            for (key, program) in compilers.iter().rev() {
+                if let Some(program) = program {
+                    cmake_args.insert(0, format!("-D{}={}", key, program));
+                }
+            }
+            Compiler::Toolchain {
+                name: file
+                    .file_stem()
+                    .map(|s| s.to_string_lossy().into_owned())
+                    .unwrap_or_default(),
+                file,
+            }
+        }
+        None => Compiler::Custom {
+            name: cxx
+                .as_ref()
+                .or(c.as_ref())
+                .map(|program| file_name(Path::new(program)))
+                .unwrap_or_else(|| "default".to_string()),
+            c,
+            cxx,
+        },
+    };
+    Ok(Some(Config {
+        name,
+        compiler,
+        build_type,
+        cmake_args,
+        build_system,
+        env,
+    }))
+}
+
+/// Configurations for the configure presets of a project.
+///
+/// Hidden presets and the ones we exported ourselves are left out.
+pub fn configs(proj_dir: &Path) -> Result<Vec<Config>, String> {
+    let mut presets = Vec::new();
+    let mut seen = Vec::new();
+    for file in &["CMakePresets.json", FILE_NAME] {
+        let path = proj_dir.join(file);
+        if path.exists() {
+            load(&path, &mut presets, &mut seen)?;
+        }
+    }
+    if seen.is_empty() {
+        return Err(format!("There is no CMakePresets.json in {:?}", proj_dir));
+    }
+    let all: HashMap<&str, &Value> = presets
+        .iter()
+        .filter_map(|(preset, _)| Some((preset["name"].as_str()?, preset)))
+        .collect();
+    let mut configs = Vec::new();
+    for (preset, file_dir) in &presets {
+        if preset["hidden"].as_bool() == Some(true) || is_ours(preset) {
+            continue;
+        }
+        let name = preset["name"].as_str().unwrap_or("");
+        let resolved = resolve(name, &all, 0)?;
+        if let Some(conf) = config(&resolved, file_dir, proj_dir)? {
+            configs.push(conf);
+        }
+    }
+    if configs.is_empty() {
+        return Err(format!(
+            "The presets of {:?} have no usable configure presets",
+            proj_dir
+        ));
+    }
+    Ok(configs)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn macros<'a>(env: &'a [(String, String)]) -> Macros<'a> {
+        Macros {
+            source_dir: Path::new("/src/proj"),
+            file_dir: Path::new("/src/proj/cmake"),
+            preset_name: "dev",
+            generator: "Ninja",
+            env,
+        }
+    }
+
+    #[test]
+    fn expand() {
+        let env = [("MKQCB_TEST_VAR".to_string(), "set".to_string())];
+        let macros = macros(&env);
+        assert_eq!(
+            macros.expand("${sourceDir}/build/${presetName}-${generator}"),
+            "/src/proj/build/dev-Ninja"
+        );
+        assert_eq!(
+            macros.expand("${sourceParentDir} ${sourceDirName} ${fileDir}"),
+            "/src proj /src/proj/cmake"
+        );
+        assert_eq!(macros.expand("$env{MKQCB_TEST_VAR}${dollar}"), "set$");
+        assert_eq!(
+            macros.expand("${unknown} $ $vendor{x} ${sourceDir"),
+            "${unknown} $ $vendor{x} ${sourceDir"
+        );
+    }
+
+    fn presets(presets: &Value) -> HashMap<&str, &Value> {
+        presets
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|p| (p["name"].as_str().unwrap(), p))
+            .collect()
+    }
+
+    #[test]
+    fn inheriting() {
+        let all = json!([
+            {"name": "base", "hidden": true, "generator": "Ninja",
+             "cacheVariables": {"A": "base", "B": "base"}},
+            {"name": "other", "generator": "Unix Makefiles", "binaryDir": "other",
+             "cacheVariables": {"B": "other", "C": "other"}},
+            {"name": "dev", "inherits": ["base", "other"], "cacheVariables": {"A": "dev"}},
+        ]);
+        let resolved = resolve("dev", &presets(&all), 0).unwrap();
+        assert_eq!(resolved["generator"], "Ninja");
+        assert_eq!(resolved["binaryDir"], "other");
+        assert_eq!(
+            resolved["cacheVariables"],
+            json!({"A": "dev", "B": "base", "C": "other"})
+        );
+        assert!(!resolved.contains_key("hidden"));
+        assert!(!resolved.contains_key("inherits"));
+    }
+
+    #[test]
+    fn inheriting_errors() {
+        let all = json!([
+            {"name": "a", "inherits": "b"},
+            {"name": "b", "inherits": "a"},
+            {"name": "c", "inherits": "missing"},
+        ]);
+        let all = presets(&all);
+        let cycle = resolve("a", &all, 0).unwrap_err();
+        assert!(cycle.contains("inherits from itself"), "{}", cycle);
+        let unknown = resolve("c", &all, 0).unwrap_err();
+        assert!(unknown.contains("Unknown preset"), "{}", unknown);
+    }
+}
@@ -0,0 +1,124 @@
+//! What compilers can do, found out by building a tiny program with them.
+//!
+//! The answers are kept in the user's cache directory by compiler, until the compiler changes.
+
+use serde_json::{Map, Value};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+use std::time::UNIX_EPOCH;
+
+const FILE_NAME: &str = "capabilities.json";
+
+pub struct Probe {
+    path: Option<PathBuf>,
+    /// The modification time and the answers of each compiler, by path
+    compilers: Map<String, Value>,
+    changed: bool,
+}
+
+impl Probe {
+    pub fn load() -> Probe {
+        let path = super::cache_dir().map(|dir| dir.join(FILE_NAME));
+        let compilers = path
+            .as_ref()
+            .and_then(|path| std::fs::read_to_string(path).ok())
+            .and_then(|text| serde_json::from_str::<Value>(&text).ok())
+            .and_then(|probed| probed.as_object().cloned())
+            .unwrap_or_default();
+        Probe {
+            path,
+            compilers,
+            changed: false,
+        }
+    }
+    /// Whether `compiler` builds a program with `flag`, linking included.
+    ///
+    /// `cxx` is whether it's a C++ compiler. A compiler that isn't there supports everything, as
+    /// far as this goes.
+    pub fn supports(&mut self, compiler: &str, cxx: bool, flag: &str) -> bool {
+        let path = if Path::new(compiler).components().count() > 1 {
+            PathBuf::from(compiler)
+        } else {
+            match super::find_in_path(compiler) {
+                Some(path) => path,
+                None => return true,
+            }
+        };
+        let modified = std::fs::metadata(&path)
+            .and_then(|meta| meta.modified())
+            .ok()
+            .and_then(|time| time.duration_since(UNIX_EPOCH).ok())
+            .map_or(0, |time| time.as_secs());
+        let key = path.display().to_string();
+        let entry = self.compilers.entry(key).or_insert_with(|| json!({}));
+        // Upgraded since, so the answers may have changed
+        if entry["modified"].as_u64() != Some(modified) || !entry["flags"].is_object() {
+            *entry = json!({ "modified": modified, "flags": {} });
+        }
+        if let Some(supported) = entry["flags"][flag].as_bool() {
+            return supported;
+        }
+        let supported = build(&path, cxx, flag);
+        super::log::debug(&format!(
+            "{} {} {}",
+            path.display(),
+            if supported {
+                "supports"
+            } else {
+                "doesn't support"
+            },
+            flag
+        ));
+        entry["flags"][flag] = Value::Bool(supported);
+        self.changed = true;
+        supported
+    }
+    /// Remembers the answers for next time, unless there was nothing new.
+    pub fn save(&self) {
+        let path = match self.path {
+            Some(ref path) if self.changed => path,
+            _ => return,
+        };
+        // Probing again is all it costs, so errors are ignored
+        if let Some(dir) = path.parent() {
+            let _ = std::fs::create_dir_all(dir);
+        }
+        let text = serde_json::to_string_pretty(&self.compilers).unwrap() + "\n";
+        let _ = std::fs::write(path, text);
+    }
+}
+
+/// Builds a program using a bit of the standard library with `compiler` and `flag`.
+fn build(compiler: &Path, cxx: bool, flag: &str) -> bool {
+    let (language, source) = if cxx {
+        (
+            "c++",
+            "#include <vector>\nint main() { return std::vector<int>(1).size() - 1; }\n",
+        )
+    } else {
+        (
+            "c",
+            "#include <stdlib.h>\nint main(void) { return abs(0); }\n",
+        )
+    };
+    let output = std::env::temp_dir().join(format!("mkqcb-probe-{}", std::process::id()));
+    let child = Command::new(compiler)
+        .args(["-x", language, "-", "-o"])
+        .arg(&output)
+        .arg(flag)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .spawn();
+    let mut child = match child {
+        Ok(child) => child,
+        Err(_) => return false,
+    };
+    if let Some(mut stdin) = child.stdin.take() {
+        let _ = stdin.write_all(source.as_bytes());
+    }
+    let built = child.wait().is_ok_and(|status| status.success());
+    let _ = std::fs::remove_file(&output);
+    built
+}
@@ -0,0 +1,123 @@
+//! Registering configurations with Qt Creator through `CMakeLists.txt.user`.
+
+use super::{warn, Config, Layout};
+use std::path::{Path, PathBuf};
+
+pub const FILE_NAME: &str = "CMakeLists.txt.user";
+
+/// Lets us tell our own files apart from ones Qt Creator wrote.
+const MARKER: &str = "<!-- Written by mkqcb -->";
+
+/// Whether `CMakeLists.txt.user` of the project is one we wrote.
+pub fn exported(proj_dir: &Path) -> bool {
+    std::fs::read_to_string(proj_dir.join(FILE_NAME)).is_ok_and(|text| text.contains(MARKER))
+}
+
+fn settings_dir() -> Option<PathBuf> {
+    Some(super::config_home()?.join("QtProject"))
+}
+
+/// The text of the first `<value>` after `<variable>name</variable>`.
+fn xml_variable(xml: &str, name: &str) -> Option<String> {
+    let variable = format!("<variable>{}</variable>", name);
+    let rest = &xml[xml.find(&variable)? + variable.len()..];
+    let start = rest.find("<value")?;
+    let rest = &rest[start + rest[start..].find('>')? + 1..];
+    Some(rest[..rest.find('<')?].trim().to_string())
+}
+
+/// The id of Qt Creator's default kit, which the configurations get registered with.
+fn default_kit() -> Option<String> {
+    let profiles = std::fs::read_to_string(settings_dir()?.join("qtcreator/profiles.xml")).ok()?;
+    xml_variable(&profiles, "Profile.Default")
+}
+
+/// Without a matching environment id, Qt Creator asks whether to use the settings.
+fn environment_id() -> Option<String> {
+    let ini = std::fs::read_to_string(settings_dir()?.join("QtCreator.ini")).ok()?;
+    let line = ini
+        .lines()
+        .find(|line| line.starts_with("Settings\\EnvironmentId="))?;
+    let value = &line["Settings\\EnvironmentId=".len()..];
+    let value = value
+        .strip_prefix("@ByteArray(")
+        .and_then(|v| v.strip_suffix(')'))
+        .unwrap_or(value);
+    Some(value.to_string())
+}
+
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+fn build_configuration(index: usize, conf: &Config, dir: &Path) -> String {
+    let build_type = conf.build_type.map_or("", |b| b.name());
+    format!(
+        "   <valuemap type=\"QVariantMap\" key=\"ProjectExplorer.Target.BuildConfiguration.{}\">
+    <value type=\"QString\" key=\"ProjectExplorer.BuildConfiguration.BuildDirectory\">{}</value>
+    <value type=\"QString\" key=\"ProjectExplorer.ProjectConfiguration.DisplayName\">{}</value>
+    <value type=\"QString\" key=\"ProjectExplorer.ProjectConfiguration.Id\">\
+CMakeProjectManager.CMakeBuildConfiguration</value>
+    <value type=\"QString\" key=\"CMake.Build.Type\">{}</value>
+   </valuemap>
+",
+        index,
+        escape(&dir.to_string_lossy()),
+        escape(&conf.name),
+        escape(build_type)
+    )
+}
+
+/// Writes `CMakeLists.txt.user` with a build configuration for every one of `configs`.
+///
+/// A file Qt Creator wrote itself is left alone, since it has the user's settings in it.
+pub fn write(proj_dir: &Path, layout: &Layout, configs: &[Config]) -> Result<(), String> {
+    let path = proj_dir.join(FILE_NAME);
+    if path.exists() && !exported(proj_dir) {
+        warn(&format!("Not replacing {:?}, Qt Creator wrote it", path));
+        return Ok(());
+    }
+    let root = super::canonicalize(&layout.root)
+        .map_err(|e| format!("Failed to find {:?}: {}", layout.root, e))?;
+    let kit = default_kit().unwrap_or_else(|| {
+        warn("Couldn't find Qt Creator's default kit, it will ask for one");
+        String::new()
+    });
+    let mut xml = format!(
+        "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n<!DOCTYPE QtCreatorProject>\n{}\n<qtcreator>\n",
+        MARKER
+    );
+    if let Some(id) = environment_id() {
+        xml += &format!(
+            " <data>\n  <variable>EnvironmentId</variable>\n  \
+             <value type=\"QByteArray\">{}</value>\n </data>\n",
+            escape(&id)
+        );
+    }
+    xml += " <data>\n  <variable>ProjectExplorer.Project.ActiveTarget</variable>\n  \
+            <value type=\"qlonglong\">0</value>\n </data>\n";
+    xml += " <data>\n  <variable>ProjectExplorer.Project.Target.0</variable>\n  \
+            <valuemap type=\"QVariantMap\">\n";
+    xml += &format!(
+        "   <value type=\"QString\" key=\"ProjectExplorer.ProjectConfiguration.Id\">{}</value>\n",
+        escape(&kit)
+    );
+    xml += "   <value type=\"qlonglong\" key=\"ProjectExplorer.Target.ActiveBuildConfiguration\">\
+            0</value>\n";
+    for (i, conf) in configs.iter().enumerate() {
+        xml += &build_configuration(i, conf, &root.join(layout.dir_name(conf)));
+    }
+    xml += &format!(
+        "   <value type=\"qlonglong\" key=\"ProjectExplorer.Target.BuildConfigurationCount\">\
+         {}</value>\n  </valuemap>\n </data>\n",
+        configs.len()
+    );
+    xml += " <data>\n  <variable>ProjectExplorer.Project.TargetCount</variable>\n  \
+            <value type=\"qlonglong\">1</value>\n </data>\n";
+    xml += " <data>\n  <variable>Version</variable>\n  <value type=\"int\">22</value>\n </data>\n";
+    xml += "</qtcreator>\n";
+    std::fs::write(&path, xml).map_err(|e| format!("Failed to write {:?}: {}", path, e))
+}
@@ -0,0 +1,83 @@
+//! `reconfigure.sh`, the cmake command lines of a build directory, to run without mkqcb.
+//!
+//! The toolchain files of Conan and vcpkg get passed like mkqcb passed them, but `conan install`
+//! isn't run again, what it installed has to be there still.
+
+use super::command_line;
+use std::path::Path;
+use std::process::Command;
+
+/// A PowerShell script on Windows, where there's no `sh` to count on.
+pub const FILE_NAME: &str = if cfg!(windows) {
+    "reconfigure.ps1"
+} else {
+    "reconfigure.sh"
+};
+
+/// Writes the script to `root`, with the cmake command of every configuration by directory name.
+pub fn write(root: &Path, commands: &[(String, Command)]) -> Result<(), String> {
+    let path = root.join(FILE_NAME);
+    let text = if cfg!(windows) {
+        powershell(commands)
+    } else {
+        sh(commands)
+    };
+    std::fs::write(&path, text).map_err(|e| format!("Failed to write {:?}: {}", path, e))?;
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        let _ = std::fs::set_permissions(&path, std::fs::Permissions::from_mode(0o755));
+    }
+    Ok(())
+}
+
+fn sh(commands: &[(String, Command)]) -> String {
+    let mut text = "#!/bin/sh\n\
+                    # Configures the build directory this is in again, written by mkqcb\n\
+                    set -e\n\
+                    cd \"$(dirname \"$0\")\"\n"
+        .to_string();
+    for (dir, cmd) in commands {
+        let dir = super::shell_quote(dir);
+        text += &format!("\nmkdir -p {0}\n(cd {0} && {1})\n", dir, command_line(cmd));
+    }
+    text
+}
+
+/// Quotes `text` for PowerShell, where only the quote itself is special inside single quotes.
+pub fn quote(text: &str) -> String {
+    format!("'{}'", text.replace('\'', "''"))
+}
+
+fn powershell(commands: &[(String, Command)]) -> String {
+    let mut text = "# Configures the build directory this is in again, written by mkqcb\n\
+                    $ErrorActionPreference = 'Stop'\n\
+                    Set-Location $PSScriptRoot\n"
+        .to_string();
+    for (dir, cmd) in commands {
+        let dir = quote(dir);
+        text += &format!(
+            "\nNew-Item -ItemType Directory -Force -Path {0} | Out-Null\nPush-Location {0}\n",
+            dir
+        );
+        // The environment outlasts the command, so it gets undone after
+        let mut undo = String::new();
+        for (key, value) in cmd.get_envs() {
+            let key = key.to_string_lossy();
+            match value {
+                Some(value) => {
+                    text += &format!("$env:{} = {}\n", key, quote(&value.to_string_lossy()));
+                    undo += &format!("Remove-Item Env:{} -ErrorAction Ignore\n", key);
+                }
+                None => text += &format!("Remove-Item Env:{} -ErrorAction Ignore\n", key),
+            }
+        }
+        text += &format!("& {}", quote(&cmd.get_program().to_string_lossy()));
+        for arg in cmd.get_args() {
+            text += &format!(" {}", quote(&arg.to_string_lossy()));
+        }
+        text += "\nif ($LASTEXITCODE -ne 0) { exit $LASTEXITCODE }\nPop-Location\n";
+        text += &undo;
+    }
+    text
+}
@@ -0,0 +1,243 @@
+//! The ways projects let sanitizers be turned on.
+
+use super::cmakelists::Command;
+
+/// The sanitizers that get configurations, with the names of those and of the sanitizer.
+pub const SANITIZERS: [(&str, &str); 4] = [
+    ("Asan", "address"),
+    ("Ubsan", "undefined"),
+    ("Tsan", "thread"),
+    ("Msan", "memory"),
+];
+
+/// The variable with the runtime options of each sanitizer, and what they are unless set.
+///
+/// Stopping at the first error makes a test fail rather than print and carry on.
+pub const RUNTIME_OPTIONS: [(&str, &str, &str); 4] = [
+    (
+        "address",
+        "ASAN_OPTIONS",
+        "abort_on_error=1:detect_leaks=1:detect_stack_use_after_return=1:strict_init_order=1",
+    ),
+    (
+        "undefined",
+        "UBSAN_OPTIONS",
+        "halt_on_error=1:print_stacktrace=1",
+    ),
+    (
+        "thread",
+        "TSAN_OPTIONS",
+        "halt_on_error=1:second_deadlock_stack=1",
+    ),
+    ("memory", "MSAN_OPTIONS", "halt_on_error=1:poison_in_dtor=1"),
+];
+
+/// Those that get configurations without `--sanitizers`.
+///
+/// Msan needs every library instrumented, the standard one included, which few setups have.
+pub const DEFAULT_SANITIZERS: [&str; 3] = ["address", "undefined", "thread"];
+
+/// The options of the sanitizers-cmake modules, which `find_package(Sanitizers)` declares.
+const FIND_SANITIZERS_OPTIONS: [(&str, &str); 4] = [
+    ("address", "SANITIZE_ADDRESS"),
+    ("undefined", "SANITIZE_UNDEFINED"),
+    ("thread", "SANITIZE_THREAD"),
+    ("memory", "SANITIZE_MEMORY"),
+];
+
+pub enum Scheme {
+    /// `-DSANITIZE=address`, for projects that pass `${SANITIZE}` to `-fsanitize`
+    Sanitize,
+    /// `-DECM_ENABLE_SANITIZERS=address`, with the ECMEnableSanitizers module of KDE
+    Ecm,
+    /// `-DUSE_SANITIZER=Address`, with the sanitizers module of cmake-scripts
+    UseSanitizer,
+    /// An option for every sanitizer, like `ENABLE_ASAN`, by sanitizer
+    Options(Vec<(String, String)>),
+}
+
+impl Scheme {
+    /// How the project with `commands`, declaring `options`, turns on sanitizers, if it can.
+    pub fn detect(commands: &[Command], options: &[&str]) -> Option<Scheme> {
+        let args = || commands.iter().flat_map(|c| c.args.iter());
+        if args().any(|arg| arg.contains("${SANITIZE}")) {
+            return Some(Scheme::Sanitize);
+        }
+        let includes = |module: &str| {
+            commands
+                .iter()
+                .any(|c| c.name == "include" && c.args.first().is_some_and(|arg| arg == module))
+        };
+        if includes("ECMEnableSanitizers") {
+            return Some(Scheme::Ecm);
+        }
+        if args().any(|arg| arg.contains("USE_SANITIZER")) {
+            return Some(Scheme::UseSanitizer);
+        }
+        if commands.iter().any(|c| {
+            c.name == "find_package" && c.args.first().is_some_and(|arg| arg == "Sanitizers")
+        }) {
+            let options = FIND_SANITIZERS_OPTIONS
+                .iter()
+                .map(|&(sanitizer, option)| (sanitizer.to_string(), option.to_string()));
+            return Some(Scheme::Options(options.collect()));
+        }
+        let mut by_sanitizer: Vec<(String, String)> = Vec::new();
+        for option in options {
+            if let Some(sanitizer) = option_sanitizer(option) {
+                if !by_sanitizer.iter().any(|(s, _)| s == sanitizer) {
+                    by_sanitizer.push((sanitizer.to_string(), option.to_string()));
+                }
+            }
+        }
+        if by_sanitizer.is_empty() {
+            None
+        } else {
+            Some(Scheme::Options(by_sanitizer))
+        }
+    }
+    /// The cache argument that turns on `sanitizer`, unless the project can't.
+    pub fn cmake_arg(&self, sanitizer: &str) -> Option<String> {
+        match *self {
+            Scheme::Sanitize => Some(format!("-DSANITIZE={}", sanitizer)),
+            Scheme::Ecm => Some(format!("-DECM_ENABLE_SANITIZERS={}", sanitizer)),
+            // It wants them capitalized
+            Scheme::UseSanitizer => Some(format!(
+                "-DUSE_SANITIZER={}{}",
+                sanitizer[..1].to_uppercase(),
+                &sanitizer[1..]
+            )),
+            Scheme::Options(ref options) => options
+                .iter()
+                .find(|(s, _)| s == sanitizer)
+                .map(|(_, option)| format!("-D{}:BOOL=ON", option)),
+        }
+    }
+}
+
+/// The sanitizer an option like `ENABLE_ASAN` or `SANITIZE_THREAD` is for, if any.
+fn option_sanitizer(option: &str) -> Option<&'static str> {
+    let option = option.to_uppercase();
+    let words: Vec<&str> = option.split(|c: char| !c.is_ascii_alphanumeric()).collect();
+    let has = |word: &str| words.contains(&word);
+    let sanitizer = words.iter().any(|word| word.starts_with("SANITIZ"));
+    if has("ASAN") || (sanitizer && has("ADDRESS")) {
+        Some("address")
+    } else if has("UBSAN") || (sanitizer && has("UNDEFINED")) {
+        Some("undefined")
+    } else if has("TSAN") || (sanitizer && has("THREAD")) {
+        Some("thread")
+    } else if has("MSAN") || (sanitizer && has("MEMORY")) {
+        Some("memory")
+    } else {
+        None
+    }
+}
+
+/// The sanitizers a list like `address,undefined` or `Asan,Ubsan` names, `none` for none.
+pub fn parse_list(list: &str) -> Result<Vec<&'static str>, String> {
+    if list == "none" {
+        return Ok(Vec::new());
+    }
+    let mut sanitizers = Vec::new();
+    for name in list.split(',').map(str::trim) {
+        let found = SANITIZERS
+            .iter()
+            .find(|&&(config, sanitizer)| name == sanitizer || name.eq_ignore_ascii_case(config));
+        match found {
+            Some(&(_, sanitizer)) if !sanitizers.contains(&sanitizer) => sanitizers.push(sanitizer),
+            Some(_) => {}
+            None => {
+                let names: Vec<&str> = SANITIZERS.iter().map(|&(_, s)| s).collect();
+                return Err(format!(
+                    "Unknown sanitizer {:?}, there are {} and none",
+                    name,
+                    names.join(", ")
+                ));
+            }
+        }
+    }
+    Ok(sanitizers)
+}
+
+/// The `-fsanitize` names of the sanitizers that cache arguments `args` turn on.
+pub fn from_args(args: &[String]) -> Vec<String> {
+    let mut sanitizers = Vec::new();
+    for arg in args {
+        let define = match arg.strip_prefix("-D") {
+            Some(define) => define,
+            None => continue,
+        };
+        let (key, value) = match define.find('=') {
+            Some(pos) => (&define[..pos], &define[pos + 1..]),
+            None => continue,
+        };
+        let key = key.split(':').next().unwrap_or(key);
+        match key {
+            "SANITIZE" | "ECM_ENABLE_SANITIZERS" | "USE_SANITIZER" => {
+                for sanitizer in value.split([';', ',']).filter(|s| !s.is_empty()) {
+                    // cmake-scripts has a MemoryWithOrigins variant
+                    let sanitizer = sanitizer.to_lowercase().replace("withorigins", "");
+                    sanitizers.push(sanitizer);
+                }
+            }
+            _ if value == "ON" => {
+                if let Some(sanitizer) = option_sanitizer(key) {
+                    sanitizers.push(sanitizer.to_string());
+                }
+            }
+            _ => {}
+        }
+    }
+    sanitizers
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn args(args: &[&str]) -> Vec<String> {
+        args.iter().map(|s| s.to_string()).collect()
+    }
+
+    #[test]
+    fn lists() {
+        assert_eq!(parse_list("none"), Ok(Vec::new()));
+        assert_eq!(
+            parse_list("address, Ubsan,asan"),
+            Ok(vec!["address", "undefined"])
+        );
+        assert_eq!(parse_list("MSAN,thread"), Ok(vec!["memory", "thread"]));
+        assert!(parse_list("address,leak").is_err());
+        assert!(parse_list("").is_err());
+    }
+
+    #[test]
+    fn sanitizers_of_args() {
+        assert_eq!(
+            from_args(&args(&[
+                "-DSANITIZE=address;undefined",
+                "-DCMAKE_BUILD_TYPE=Debug",
+                "-DUSE_SANITIZER:STRING=MemoryWithOrigins",
+            ])),
+            ["address", "undefined", "memory"]
+        );
+        assert_eq!(
+            from_args(&args(&["-DECM_ENABLE_SANITIZERS=address,leak"])),
+            ["address", "leak"]
+        );
+        assert!(from_args(&args(&["-DSANITIZE", "SANITIZE=address"])).is_empty());
+    }
+    #[test]
+    fn sanitizers_of_options() {
+        assert_eq!(
+            from_args(&args(&[
+                "-DENABLE_ASAN:BOOL=ON",
+                "-DSANITIZE_THREAD=ON",
+                "-DENABLE_UBSAN=OFF",
+                "-DUSE_MEMORY=ON",
+            ])),
+            ["address", "thread"]
+        );
+    }
+}
@@ -0,0 +1,89 @@
+//! `mkqcb-stats.json`, how long the configurations of a build directory took in earlier runs.
+
+use serde_json::{Map, Value};
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+pub const FILE_NAME: &str = "mkqcb-stats.json";
+
+/// How many runs of each configuration are kept.
+const KEPT_RUNS: usize = 20;
+
+#[derive(Clone, Copy)]
+pub struct Timing {
+    pub configure: Duration,
+    /// Unless it didn't get built
+    pub build: Option<Duration>,
+}
+
+pub struct Stats {
+    path: PathBuf,
+    /// The runs of each configuration, oldest first
+    configs: Map<String, Value>,
+}
+
+impl Stats {
+    /// What the build directory `root` has on record, which may well be nothing.
+    pub fn load(root: &Path) -> Stats {
+        let path = root.join(FILE_NAME);
+        let configs = std::fs::read_to_string(&path)
+            .ok()
+            .and_then(|text| serde_json::from_str::<Value>(&text).ok())
+            .and_then(|stats| stats["configurations"].as_object().cloned())
+            .unwrap_or_default();
+        Stats { path, configs }
+    }
+    /// How long `name` took the last time it was created.
+    pub fn last(&self, name: &str) -> Option<Timing> {
+        let run = self.configs.get(name)?.as_array()?.last()?;
+        Some(Timing {
+            configure: Duration::try_from_secs_f64(run["configure"].as_f64()?).ok()?,
+            build: run["build"]
+                .as_f64()
+                .and_then(|build| Duration::try_from_secs_f64(build).ok()),
+        })
+    }
+    pub fn record(&mut self, name: &str, timing: Timing) {
+        let runs = self.configs.entry(name).or_insert_with(|| json!([]));
+        if !runs.is_array() {
+            *runs = json!([]);
+        }
+        let runs = runs.as_array_mut().unwrap();
+        runs.push(json!({
+            "configure": timing.configure.as_secs_f64(),
+            "build": timing.build.map(|build| build.as_secs_f64()),
+        }));
+        if runs.len() > KEPT_RUNS {
+            let excess = runs.len() - KEPT_RUNS;
+            runs.drain(..excess);
+        }
+    }
+    pub fn save(&self) -> Result<(), String> {
+        let stats = json!({ "configurations": self.configs });
+        let text = serde_json::to_string_pretty(&stats).unwrap() + "\n";
+        std::fs::write(&self.path, text)
+            .map_err(|e| format!("Failed to write {:?}: {}", self.path, e))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn invalid_durations() {
+        let root = std::env::temp_dir().join(format!("mkqcb-stats-{}", std::process::id()));
+        std::fs::create_dir_all(&root).unwrap();
+        let text = r#"{"configurations": {
+            "A": [{"configure": -1, "build": 2}],
+            "B": [{"configure": 1.5, "build": -2}]
+        }}"#;
+        std::fs::write(root.join(FILE_NAME), text).unwrap();
+        let stats = Stats::load(&root);
+        let _ = std::fs::remove_dir_all(&root);
+        assert!(stats.last("A").is_none());
+        let b = stats.last("B").unwrap();
+        assert_eq!(b.configure, Duration::from_millis(1500));
+        assert!(b.build.is_none());
+    }
+}
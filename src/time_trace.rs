@@ -0,0 +1,113 @@
+//! Summing up the `-ftime-trace` files Clang writes next to the object files it compiles.
+//!
+//! Each is a Chrome trace of one translation unit, with an event for every header it parsed and
+//! every template it instantiated.
+
+use serde_json::Value;
+use std::collections::HashMap;
+use std::path::Path;
+use std::time::Duration;
+
+/// What some header or template cost, over all translation units.
+pub struct Cost {
+    pub name: String,
+    pub time: Duration,
+    /// Of the translation units, how many had it
+    pub units: usize,
+}
+
+pub struct Report {
+    /// Each translation unit with how long it took, slowest first
+    pub units: Vec<(String, Duration)>,
+    /// The time spent including each header, those it includes in turn included
+    pub headers: Vec<Cost>,
+    pub templates: Vec<Cost>,
+}
+
+/// Adds up the traces under the build directory `dir`.
+pub fn collect(dir: &Path) -> Result<Report, String> {
+    let mut files = Vec::new();
+    find_traces(dir, &mut files).map_err(|e| format!("Failed to read {:?}: {}", dir, e))?;
+    let mut units = Vec::new();
+    let mut headers = HashMap::new();
+    let mut templates = HashMap::new();
+    for path in files {
+        let events = match read_events(&path) {
+            Some(events) => events,
+            None => continue,
+        };
+        let name = path.strip_prefix(dir).unwrap_or(&path).with_extension("");
+        let mut total = Duration::ZERO;
+        let mut unit_headers = HashMap::new();
+        let mut unit_templates = HashMap::new();
+        for event in &events {
+            let (name, time) = match (event["name"].as_str(), event["dur"].as_u64()) {
+                (Some(name), Some(dur)) => (name, Duration::from_micros(dur)),
+                _ => continue,
+            };
+            let detail = event["args"]["detail"].as_str().unwrap_or("");
+            match name {
+                "ExecuteCompiler" => total = total.max(time),
+                "Source" => *unit_headers.entry(detail).or_default() += time,
+                "InstantiateClass" | "InstantiateFunction" => {
+                    *unit_templates.entry(detail).or_default() += time
+                }
+                _ => {}
+            }
+        }
+        add_unit(&mut headers, unit_headers);
+        add_unit(&mut templates, unit_templates);
+        units.push((name.display().to_string(), total));
+    }
+    units.sort_by_key(|&(_, time)| std::cmp::Reverse(time));
+    Ok(Report {
+        units,
+        headers: sorted(headers),
+        templates: sorted(templates),
+    })
+}
+
+/// The `.json` files under `dir`, which the build's own json files are among.
+fn find_traces(dir: &Path, files: &mut Vec<std::path::PathBuf>) -> std::io::Result<()> {
+    for entry in std::fs::read_dir(dir)? {
+        let entry = entry?;
+        let path = entry.path();
+        // Not following symlinks, so there is no going around in circles
+        if entry.file_type()?.is_dir() {
+            find_traces(&path, files)?;
+        } else if path.extension().is_some_and(|ext| ext == "json") {
+            files.push(path);
+        }
+    }
+    Ok(())
+}
+
+/// The events of the trace at `path`, if it is one.
+fn read_events(path: &Path) -> Option<Vec<Value>> {
+    let text = std::fs::read_to_string(path).ok()?;
+    match serde_json::from_str::<Value>(&text).ok()? {
+        Value::Object(mut trace) => match trace.remove("traceEvents")? {
+            Value::Array(events) => Some(events),
+            _ => None,
+        },
+        _ => None,
+    }
+}
+
+fn add_unit(costs: &mut HashMap<String, (Duration, usize)>, unit: HashMap<&str, Duration>) {
+    for (name, time) in unit {
+        let cost = costs.entry(name.to_string()).or_default();
+        cost.0 += time;
+        cost.1 += 1;
+    }
+}
+
+fn sorted(costs: HashMap<String, (Duration, usize)>) -> Vec<Cost> {
+    let mut costs: Vec<Cost> = costs
+        .into_iter()
+        .filter(|(name, _)| !name.is_empty())
+        .map(|(name, (time, units))| Cost { name, time, units })
+        .collect();
+    costs.sort_by(|a, b| b.time.cmp(&a.time).then_with(|| a.name.cmp(&b.name)));
+    costs
+}
@@ -0,0 +1,119 @@
+//! Using vcpkg for the dependencies of a project.
+
+use super::{canonicalize, cmake_path, find_in_path, Compiler, Config};
+use std::path::{Path, PathBuf};
+
+/// Where configurations keep the triplets made for them.
+const TRIPLETS_DIR: &str = "vcpkg-triplets";
+
+/// The vcpkg installation to use for the project, if it uses vcpkg.
+///
+/// That's the case when `VCPKG_ROOT` is set, or it has a manifest.
+pub fn root(proj_dir: &Path) -> Result<Option<PathBuf>, String> {
+    if let Some(root) = std::env::var_os("VCPKG_ROOT").filter(|root| !root.is_empty()) {
+        return Ok(Some(PathBuf::from(root)));
+    }
+    if !proj_dir.join("vcpkg.json").is_file() {
+        return Ok(None);
+    }
+    let vcpkg = find_in_path("vcpkg").and_then(|vcpkg| canonicalize(vcpkg).ok());
+    match vcpkg.as_ref().and_then(|vcpkg| vcpkg.parent()) {
+        Some(root) => Ok(Some(root.to_path_buf())),
+        None => Err(
+            "The project has a vcpkg.json, but VCPKG_ROOT isn't set and vcpkg isn't in the PATH"
+                .to_string(),
+        ),
+    }
+}
+
+/// The triplet vcpkg picks by default on this machine.
+fn host_triplet() -> String {
+    let arch = match std::env::consts::ARCH {
+        "x86_64" => "x64",
+        "aarch64" => "arm64",
+        arch => arch,
+    };
+    let os = match std::env::consts::OS {
+        "macos" => "osx",
+        // The community triplets of MSYS2 and other MinGW toolchains
+        "windows" if cfg!(target_env = "gnu") => "mingw-dynamic",
+        os => os,
+    };
+    format!("{}-{}", arch, os)
+}
+
+/// The cmake arguments that make `conf` use vcpkg from `root`.
+///
+/// Dependencies get built with the compiler and sanitizers of the configuration, through a
+/// triplet written to its directory `dir`.
+pub fn cmake_args(conf: &Config, root: &Path, dir: &Path) -> Result<Vec<String>, String> {
+    let toolchain = root.join("scripts/buildsystems/vcpkg.cmake");
+    let mut args = vec![format!("-DCMAKE_TOOLCHAIN_FILE={}", toolchain.display())];
+    let (c, cxx) = match conf.compiler {
+        // Cross compiling needs a triplet for the target, which is the user's to pick
+        Compiler::Toolchain { ref file, .. } => {
+            args.push(format!(
+                "-DVCPKG_CHAINLOAD_TOOLCHAIN_FILE={}",
+                file.display()
+            ));
+            return Ok(args);
+        }
+        Compiler::Gcc => (None, None),
+        Compiler::Clang => (Some("clang".to_string()), Some("clang++".to_string())),
+        Compiler::Custom { ref c, ref cxx, .. } => (c.clone(), cxx.clone()),
+    };
+    let sanitizers = super::sanitizers::from_args(&conf.cmake_args);
+    if c.is_none() && cxx.is_none() && sanitizers.is_empty() {
+        return Ok(args);
+    }
+    let base = host_triplet();
+    let triplet = format!("{}-mkqcb", base);
+    let triplets_dir = std::env::current_dir()
+        .unwrap()
+        .join(dir)
+        .join(TRIPLETS_DIR);
+    let mut text = format!(
+        "include(\"{}\")\n",
+        cmake_path(&root.join("triplets").join(format!("{}.cmake", base)))
+    );
+    if c.is_some() || cxx.is_some() {
+        let compilers = triplets_dir.join("compilers.cmake");
+        let mut compilers_text = String::new();
+        if let Some(ref c) = c {
+            compilers_text += &format!("set(CMAKE_C_COMPILER \"{}\")\n", cmake_path(Path::new(c)));
+        }
+        if let Some(ref cxx) = cxx {
+            compilers_text += &format!(
+                "set(CMAKE_CXX_COMPILER \"{}\")\n",
+                cmake_path(Path::new(cxx))
+            );
+        }
+        write(&compilers, &compilers_text)?;
+        text += &format!(
+            "set(VCPKG_CHAINLOAD_TOOLCHAIN_FILE \"{}\")\n",
+            cmake_path(&compilers)
+        );
+    }
+    if !sanitizers.is_empty() {
+        let flag = format!("-fsanitize={}", sanitizers.join(","));
+        text += &format!(
+            "set(VCPKG_C_FLAGS \"{0}\")\nset(VCPKG_CXX_FLAGS \"{0}\")\n\
+             set(VCPKG_LINKER_FLAGS \"{0}\")\n",
+            flag
+        );
+    }
+    write(&triplets_dir.join(format!("{}.cmake", triplet)), &text)?;
+    args.push(format!(
+        "-DVCPKG_OVERLAY_TRIPLETS={}",
+        triplets_dir.display()
+    ));
+    args.push(format!("-DVCPKG_TARGET_TRIPLET={}", triplet));
+    Ok(args)
+}
+
+fn write(path: &Path, text: &str) -> Result<(), String> {
+    if let Some(dir) = path.parent() {
+        std::fs::create_dir_all(dir).map_err(|e| format!("Failed to create {:?}: {}", dir, e))?;
+    }
+    std::fs::write(path, text).map_err(|e| format!("Failed to write {:?}: {}", path, e))
+}